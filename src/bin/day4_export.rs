@@ -0,0 +1,38 @@
+use failure::{bail, Error};
+use std::env;
+use std::fs;
+use uberjay_aoc::day4;
+
+const USAGE: &str = "usage: day4_export <json|csv> [input file]
+       day4_export import <csv file>";
+
+fn main() -> Result<(), Error> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mode = args.first().map(String::as_str);
+
+    match mode {
+        Some("json") | Some("csv") => {
+            let path = args.get(1).map_or("input/2018/day4.txt", String::as_str);
+            let shifts = day4::input_generator(&fs::read_to_string(path)?);
+
+            if mode == Some("json") {
+                println!("{}", day4::to_json_lines(&shifts)?);
+            } else {
+                print!("{}", day4::to_csv(&shifts)?);
+            }
+        }
+        Some("import") => {
+            let path = match args.get(1) {
+                Some(path) => path,
+                None => bail!("{}", USAGE),
+            };
+            let shifts = day4::from_csv(&fs::read_to_string(path)?)?;
+
+            println!("part1: {}", day4::solve_part1(&shifts));
+            println!("part2: {}", day4::solve_part2(&shifts));
+        }
+        _ => bail!("{}", USAGE),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,14 @@
+use failure::Error;
+use std::io;
+use uberjay_aoc::day5::PolymerReducer;
+
+fn main() -> Result<(), Error> {
+    let stdin = io::stdin();
+    let mut reducer = PolymerReducer::new();
+    reducer.read_from(stdin.lock())?;
+
+    println!("part1: {}", reducer.len());
+    println!("part2: {}", reducer.best_removal_len());
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use failure::Error;
+use std::fs;
+use uberjay_aoc::cli::Args;
+use uberjay_aoc::day6::{self, RenderOptions};
+
+const USAGE: &str = "usage: day6_render [--ppm FILE] [--scale N] [--safe N] [--no-outline] \
+                     [input file]";
+
+fn main() -> Result<(), Error> {
+    let mut opts = RenderOptions::default();
+    let mut ppm_path = None;
+    let mut path = "input/2018/day6.txt".to_owned();
+    let mut args = Args::from_env(USAGE);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ppm" => ppm_path = Some(args.value::<String>()?),
+            "--scale" => opts.scale = args.value()?,
+            "--safe" => opts.safe_threshold = Some(args.value()?),
+            "--no-outline" => opts.outline_infinite = false,
+            _ => path = args.positional(arg)?,
+        }
+    }
+
+    let points = day6::input_generator(&fs::read_to_string(&path)?)?;
+
+    match ppm_path {
+        Some(out) => fs::write(out, day6::render_ppm(&points, &opts))?,
+        None => println!("{}", day6::render_ascii(&points, &opts)),
+    }
+
+    Ok(())
+}
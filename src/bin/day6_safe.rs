@@ -0,0 +1,24 @@
+use failure::Error;
+use std::fs;
+use uberjay_aoc::cli::Args;
+use uberjay_aoc::day6;
+
+const USAGE: &str = "usage: day6_safe [--threshold N] [input file]";
+
+fn main() -> Result<(), Error> {
+    let mut threshold = day6::SAFE_THRESHOLD;
+    let mut path = "input/2018/day6.txt".to_owned();
+    let mut args = Args::from_env(USAGE);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--threshold" | "-t" => threshold = args.value()?,
+            _ => path = args.positional(arg)?,
+        }
+    }
+
+    let points = day6::input_generator(&fs::read_to_string(&path)?)?;
+    println!("{}", day6::manhattan_safe_region_size(&points, threshold));
+
+    Ok(())
+}
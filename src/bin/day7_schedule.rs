@@ -0,0 +1,61 @@
+use failure::{bail, Error};
+use itertools::Itertools;
+use std::fs;
+use uberjay_aoc::cli::Args;
+use uberjay_aoc::day7::{self, Scheduler};
+
+const USAGE: &str =
+    "usage: day7_schedule [--workers N] [--base N] [--format table|csv|json|dot|dot-scheduled] \
+                     [--analyze] [input file]";
+
+fn main() -> Result<(), Error> {
+    let (mut workers, mut base, mut format) = (5, 60, "table".to_owned());
+    let mut analyze = false;
+    let mut path = "input/2018/day7.txt".to_owned();
+    let mut args = Args::from_env(USAGE);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--workers" => workers = args.value()?,
+            "--base" => base = args.value()?,
+            "--format" => format = args.value()?,
+            "--analyze" => analyze = true,
+            _ => path = args.positional(arg)?,
+        }
+    }
+
+    let edges = day7::input_generator(&fs::read_to_string(&path)?)?;
+    let scheduler = Scheduler::new(workers, base, day7::letter_duration);
+
+    if analyze {
+        let analysis = scheduler.analyze(&edges)?;
+
+        println!("Step  Duration  Earliest  Latest  Slack");
+        for (step, t) in &analysis.steps {
+            println!(
+                "{:>4}{:>10}{:>10}{:>8}{:>7}",
+                step, t.duration, t.earliest_start, t.latest_start, t.slack
+            );
+        }
+        println!();
+        println!(
+            "critical path: {}",
+            analysis.critical_path.iter().join(" -> ")
+        );
+        println!("unlimited workers: {}s", analysis.min_time);
+        println!("{} workers: {}s", workers, scheduler.run(&edges)?);
+
+        return Ok(());
+    }
+
+    match format.as_str() {
+        "dot" => print!("{}", scheduler.to_dot(&edges, false)?),
+        "dot-scheduled" => print!("{}", scheduler.to_dot(&edges, true)?),
+        "table" => print!("{}", scheduler.run_with_schedule(&edges)?.to_table()),
+        "csv" => print!("{}", scheduler.run_with_schedule(&edges)?.to_csv()?),
+        "json" => println!("{}", scheduler.run_with_schedule(&edges)?.to_json()?),
+        _ => bail!("{}", USAGE),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use failure::Error;
+use std::fs;
+use uberjay_aoc::cli::Args;
+use uberjay_aoc::day8;
+
+const USAGE: &str = "usage: day8_tree [--stream | --json] [input file]";
+
+fn main() -> Result<(), Error> {
+    let mut format = "pretty";
+    let mut path = "input/2018/day8.txt".to_owned();
+
+    let mut args = Args::from_env(USAGE);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stream" => format = "stream",
+            "--json" => format = "json",
+            _ => path = args.positional(arg)?,
+        }
+    }
+
+    let tree = day8::input_generator(fs::read_to_string(&path)?.trim_end())?;
+
+    match format {
+        "stream" => println!("{}", tree),
+        "json" => println!("{}", tree.to_json()?),
+        _ => print!("{}", tree.pretty()?),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use failure::Error;
+use std::fs;
+use uberjay_aoc::cli::Args;
+use uberjay_aoc::day9::{self, Rules};
+
+const USAGE: &str = "usage: day9_play [--multiplier N] [--special N] [--remove-offset N] \
+                     [--insert-offset N] [--scoreboard | --replay] [input file]";
+
+fn main() -> Result<(), Error> {
+    let mut rules = Rules::default();
+    let mut multiplier = 1;
+    let mut mode = "score";
+    let mut path = "input/2018/day9.txt".to_owned();
+    let mut args = Args::from_env(USAGE);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--multiplier" => multiplier = args.value()?,
+            "--special" => rules.special_multiple = args.value()?,
+            "--remove-offset" => rules.remove_offset = args.value()?,
+            "--insert-offset" => rules.insert_offset = args.value()?,
+            "--scoreboard" => mode = "scoreboard",
+            "--replay" => mode = "replay",
+            _ => path = args.positional(arg)?,
+        }
+    }
+
+    let params =
+        day9::input_generator(fs::read_to_string(&path)?.trim_end())?.scaled(multiplier)?;
+
+    match mode {
+        "replay" => print!("{}", day9::replay(&params, &rules)?),
+        "scoreboard" => {
+            let board = day9::scoreboard(&params, &rules)?;
+
+            println!("Player     Score");
+            for (player, score) in &board.scores {
+                println!("{:>6}{:>10}", player, score);
+            }
+            println!();
+            match board.winner() {
+                Some(winner) => println!("winner: player {}", winner),
+                None => println!("nobody scored"),
+            }
+            println!();
+            for event in &board.events {
+                println!(
+                    "turn {}: player {} scores {} + {}",
+                    event.turn, event.player, event.turn, event.removed
+                );
+            }
+        }
+        _ => println!("{}", day9::play_with_rules(&params, &rules)?),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use failure::{bail, format_err, Error};
+use std::env;
+use std::fmt;
+use std::iter;
+use std::str::FromStr;
+
+/// Command-line arguments for the runners in `src/bin`, read one at a time.
+/// Flags taking a value fetch it with `value`, and anything else goes through
+/// `positional`, so a missing value or unknown flag fails with the usage text.
+pub struct Args<I> {
+    args: I,
+    usage: &'static str,
+    last: String,
+}
+
+impl Args<iter::Skip<env::Args>> {
+    pub fn from_env(usage: &'static str) -> Self {
+        Args::new(usage, env::args().skip(1))
+    }
+}
+
+impl<I: Iterator<Item = String>> Args<I> {
+    pub fn new(usage: &'static str, args: I) -> Self {
+        Args {
+            args,
+            usage,
+            last: String::new(),
+        }
+    }
+
+    /// The value following the flag last returned by `next`.
+    pub fn value<T>(&mut self) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let value = match self.args.next() {
+            Some(value) => value,
+            None => bail!("{}", self.usage),
+        };
+        value
+            .parse()
+            .map_err(|e| format_err!("invalid value {:?} for {}: {}", value, self.last, e))
+    }
+
+    /// `arg` as a positional argument, which must not look like a flag.
+    pub fn positional(&self, arg: String) -> Result<String, Error> {
+        if arg.starts_with('-') {
+            bail!("{}", self.usage);
+        }
+        Ok(arg)
+    }
+}
+
+impl<I: Iterator<Item = String>> Iterator for Args<I> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let arg = self.args.next()?;
+        self.last.clone_from(&arg);
+        Some(arg)
+    }
+}
+
+#[test]
+fn test_args() {
+    const USAGE: &str = "usage: test [--count N] [--quiet] [input file]";
+    let parse = |args: &[&str]| -> Result<(u32, bool, String), Error> {
+        let (mut count, mut quiet, mut path) = (1, false, "default".to_owned());
+        let mut args = Args::new(USAGE, args.iter().map(|&a| a.to_owned()));
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--count" => count = args.value()?,
+                "--quiet" => quiet = true,
+                _ => path = args.positional(arg)?,
+            }
+        }
+        Ok((count, quiet, path))
+    };
+
+    assert_eq!(parse(&[]).unwrap(), (1, false, "default".to_owned()));
+    assert_eq!(
+        parse(&["--count", "3", "in.txt", "--quiet"]).unwrap(),
+        (3, true, "in.txt".to_owned())
+    );
+    assert_eq!(parse(&["--count"]).err().unwrap().to_string(), USAGE);
+    assert_eq!(parse(&["--verbose"]).err().unwrap().to_string(), USAGE);
+    assert_eq!(
+        parse(&["--count", "x"]).err().unwrap().to_string(),
+        "invalid value \"x\" for --count: invalid digit found in string"
+    );
+}
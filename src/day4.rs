@@ -1,11 +1,12 @@
 use chrono::prelude::*;
-use failure::{bail, Error};
+use failure::{bail, err_msg, Error};
 use hashbrown::HashMap;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use std::ops::Range;
 use std::str::FromStr;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ShiftRecord {
     pub guard: u32,
     events: Vec<Event>,
@@ -13,45 +14,132 @@ pub struct ShiftRecord {
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Event {
-    Start(DateTime<Utc>),
-    Wake(DateTime<Utc>),
-    Sleep(DateTime<Utc>),
+    Start(DateTime<FixedOffset>),
+    Wake(DateTime<FixedOffset>),
+    Sleep(DateTime<FixedOffset>),
 }
 
-impl FromStr for ShiftRecord {
+/// A single parsed log line, before it's been grouped into a shift.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EventKind {
+    BeginShift(u32),
+    Sleep,
+    Wake,
+}
+
+impl EventKind {
+    fn begins_shift(self) -> bool {
+        matches!(self, EventKind::BeginShift(_))
+    }
+
+    fn into_event(self, dt: DateTime<FixedOffset>) -> Event {
+        match self {
+            EventKind::BeginShift(_) => Event::Start(dt),
+            EventKind::Sleep => Event::Sleep(dt),
+            EventKind::Wake => Event::Wake(dt),
+        }
+    }
+}
+
+impl FromStr for EventKind {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (dt, desc) = ShiftRecord::extract_time_and_desc(s)?;
-        let desc_parts: Vec<&str> = desc.split('#').collect();
+        let desc = s.split_whitespace().collect::<Vec<&str>>();
 
-        Ok(Self {
-            guard: desc_parts[1].split_whitespace().next().unwrap().parse()?,
-            events: vec![Event::Start(dt)],
-        })
+        match desc.as_slice() {
+            ["wakes", "up"] => Ok(EventKind::Wake),
+            ["falls", "asleep"] => Ok(EventKind::Sleep),
+            ["Guard", id, "begins", "shift"] => {
+                Ok(EventKind::BeginShift(id.trim_start_matches('#').parse()?))
+            }
+            _ => bail!("unexpected event description '{}'", s),
+        }
+    }
+}
+
+/// Timestamp formats carrying an explicit offset. The offset is kept, so nap
+/// minutes are read off the log's own wall clock; timestamps still compare and
+/// sort by the instant they name.
+const ZONED_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M %z",
+    "%Y-%m-%d %H:%M:%S %z",
+    "%Y/%m/%d %H:%M %z",
+    "%Y/%m/%d %H:%M:%S %z",
+];
+
+/// Timestamp formats without an offset, which are taken to be UTC.
+const NAIVE_FORMATS: [&str; 6] = [
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y/%m/%d %H:%M",
+    "%Y/%m/%d %H:%M:%S",
+];
+
+fn parse_timestamp(s: &str) -> Result<DateTime<FixedOffset>, Error> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(dt);
+    }
+
+    if let Some(dt) = ZONED_FORMATS
+        .iter()
+        .find_map(|fmt| DateTime::parse_from_str(s, fmt).ok())
+    {
+        return Ok(dt);
+    }
+
+    NAIVE_FORMATS
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+        .map(|naive| Utc.fix().from_utc_datetime(&naive))
+        .ok_or_else(|| err_msg(format!("unrecognized timestamp '{}'", s)))
+}
+
+/// Splits a `[timestamp] description` log line into its parsed parts.
+pub fn parse_event(line: &str) -> Result<(DateTime<FixedOffset>, EventKind), Error> {
+    let line = line.trim();
+    if !line.starts_with('[') {
+        bail!("missing timestamp in line '{}'", line);
+    }
+
+    let close = line
+        .find(']')
+        .ok_or_else(|| err_msg(format!("unterminated timestamp in line '{}'", line)))?;
+
+    Ok((
+        parse_timestamp(line[1..close].trim())?,
+        line[close + 1..].parse()?,
+    ))
+}
+
+impl FromStr for ShiftRecord {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_event(s)? {
+            (dt, EventKind::BeginShift(guard)) => Ok(Self {
+                guard,
+                events: vec![Event::Start(dt)],
+            }),
+            _ => bail!("expected a shift start, found '{}'", s),
+        }
     }
 }
 
 impl ShiftRecord {
     pub fn from_lines(lines: &[&str]) -> Result<Self, Error> {
-        let mut shift: ShiftRecord = lines[0].parse().unwrap();
-        shift.add_events(&lines[1..]).unwrap();
+        let mut shift: ShiftRecord = lines[0].parse()?;
+        shift.add_events(&lines[1..])?;
         Ok(shift)
     }
 
-    fn extract_time_and_desc<'a>(line: &'a str) -> Result<(DateTime<Utc>, &'a str), Error> {
-        let parts: Vec<&str> = line.split("] ").collect();
-        let time_str = parts[0].trim_left_matches('[');
-        Ok((Utc.datetime_from_str(time_str, "%Y-%m-%d %H:%M")?, parts[1]))
-    }
-
     pub fn add_events(&mut self, lines: &[&str]) -> Result<(), Error> {
         for line in lines {
-            let (dt, desc) = ShiftRecord::extract_time_and_desc(line)?;
-            match desc {
-                "wakes up" => self.events.push(Event::Wake(dt)),
-                "falls asleep" => self.events.push(Event::Sleep(dt)),
-                _ => bail!("unexpected event description '{}'", desc),
+            match parse_event(line)? {
+                (_, EventKind::BeginShift(_)) => bail!("unexpected shift start '{}'", line),
+                (dt, kind) => self.events.push(kind.into_event(dt)),
             }
         }
         Ok(())
@@ -91,22 +179,228 @@ impl ShiftRecord {
     }
 }
 
-#[aoc_generator(day4)]
-pub fn input_generator(input: &str) -> Vec<ShiftRecord> {
-    let lines: Vec<&str> = input.lines().sorted();
-    let sorted_lines_it = lines.iter().cloned().peekable();
-    let raw_shifts = sorted_lines_it.batching(|it| match it.next() {
-        None => None,
-        Some(x) if x.contains(" begins shift") => {
-            let mut t = vec![x];
-            t.extend(it.peeking_take_while(|l| !l.contains(" begins shift")));
-            Some(t)
+/// A single row of the flattened CSV form of a shift. Each sleep/wake interval
+/// gets its own row; a shift without any naps is a row with empty interval
+/// columns.
+#[derive(Debug, Serialize, Deserialize)]
+struct ShiftRow {
+    guard: u32,
+    start: DateTime<FixedOffset>,
+    sleep: Option<DateTime<FixedOffset>>,
+    wake: Option<DateTime<FixedOffset>>,
+}
+
+/// The JSON lines form of a shift: the same data as its CSV rows, with the
+/// intervals nested under a single record.
+#[derive(Debug, Serialize, Deserialize)]
+struct ShiftJson {
+    guard: u32,
+    start: DateTime<FixedOffset>,
+    intervals: Vec<SleepInterval>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SleepInterval {
+    sleep: DateTime<FixedOffset>,
+    wake: DateTime<FixedOffset>,
+}
+
+impl From<Vec<ShiftRow>> for ShiftJson {
+    fn from(rows: Vec<ShiftRow>) -> Self {
+        ShiftJson {
+            guard: rows[0].guard,
+            start: rows[0].start,
+            intervals: rows
+                .iter()
+                .filter_map(|r| {
+                    Some(SleepInterval {
+                        sleep: r.sleep?,
+                        wake: r.wake?,
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ShiftJson {
+    fn into_rows(self) -> Vec<ShiftRow> {
+        let (guard, start) = (self.guard, self.start);
+        if self.intervals.is_empty() {
+            return vec![ShiftRow {
+                guard,
+                start,
+                sleep: None,
+                wake: None,
+            }];
         }
-        Some(x) => panic!("unexpected line in input: '{}'", x),
+
+        self.intervals
+            .into_iter()
+            .map(|i| ShiftRow {
+                guard,
+                start,
+                sleep: Some(i.sleep),
+                wake: Some(i.wake),
+            })
+            .collect()
+    }
+}
+
+impl ShiftRecord {
+    fn to_rows(&self) -> Vec<ShiftRow> {
+        let start = match self.events[0] {
+            Event::Start(dt) => dt,
+            _ => unreachable!("shift records always begin with a start event"),
+        };
+
+        let rows: Vec<ShiftRow> = self
+            .events
+            .iter()
+            .tuple_windows()
+            .filter_map(|pair| match pair {
+                (&Event::Sleep(st), &Event::Wake(wt)) => Some(ShiftRow {
+                    guard: self.guard,
+                    start,
+                    sleep: Some(st),
+                    wake: Some(wt),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        if rows.is_empty() {
+            vec![ShiftRow {
+                guard: self.guard,
+                start,
+                sleep: None,
+                wake: None,
+            }]
+        } else {
+            rows
+        }
+    }
+
+    fn from_rows(rows: &[ShiftRow]) -> Result<Self, Error> {
+        let first = rows.first().ok_or_else(|| err_msg("no rows for shift"))?;
+        let mut events = vec![Event::Start(first.start)];
+        let mut awake_since = first.start;
+
+        for row in rows {
+            match (row.sleep, row.wake) {
+                // naps must follow the start and each other without overlapping
+                (Some(st), Some(wt)) if awake_since <= st && st < wt => {
+                    awake_since = wt;
+                    events.push(Event::Sleep(st));
+                    events.push(Event::Wake(wt));
+                }
+                (None, None) if rows.len() == 1 => (),
+                _ => bail!(
+                    "invalid sleep interval for guard {} starting {}",
+                    row.guard,
+                    row.start
+                ),
+            }
+        }
+
+        Ok(Self {
+            guard: first.guard,
+            events,
+        })
+    }
+}
+
+/// Serializes shifts as JSON lines, one `{"guard", "start", "intervals":
+/// [{"sleep", "wake"}]}` record per line.
+pub fn to_json_lines(shifts: &[ShiftRecord]) -> Result<String, Error> {
+    let lines: Result<Vec<String>, _> = shifts
+        .iter()
+        .map(|s| serde_json::to_string(&ShiftJson::from(s.to_rows())))
+        .collect();
+    Ok(lines?.join("\n"))
+}
+
+/// Reads shifts back from the JSON lines written by `to_json_lines`, checking
+/// them the same way `from_csv` does.
+pub fn from_json_lines(input: &str) -> Result<Vec<ShiftRecord>, Error> {
+    input
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| {
+            let record: ShiftJson = serde_json::from_str(l)?;
+            ShiftRecord::from_rows(&record.into_rows())
+        })
+        .collect()
+}
+
+/// Serializes shifts as CSV with a `guard,start,sleep,wake` header.
+pub fn to_csv(shifts: &[ShiftRecord]) -> Result<String, Error> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+
+    for row in shifts.iter().flat_map(|s| s.to_rows()) {
+        wtr.serialize(row)?;
+    }
+
+    let data = wtr
+        .into_inner()
+        .map_err(|_| err_msg("failed to flush csv writer"))?;
+    Ok(String::from_utf8(data)?)
+}
+
+/// Reads shifts back from the CSV form written by `to_csv`. Consecutive rows
+/// with the same guard and start time belong to the same shift, and shifts are
+/// sorted by start time so the result can be fed straight to the solvers.
+pub fn from_csv(input: &str) -> Result<Vec<ShiftRecord>, Error> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(input.as_bytes());
+    let rows: Vec<ShiftRow> = rdr.deserialize().collect::<Result<_, _>>()?;
+
+    let mut shifts = rows
+        .into_iter()
+        .group_by(|r| (r.guard, r.start))
+        .into_iter()
+        .map(|(_key, group)| ShiftRecord::from_rows(&group.collect::<Vec<_>>()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    shifts.sort_by_key(|s| match s.events[0] {
+        Event::Start(dt) => dt,
+        _ => unreachable!("shift records always begin with a start event"),
     });
 
-    raw_shifts
-        .map(|shift_lines| ShiftRecord::from_lines(&shift_lines).unwrap())
+    Ok(shifts)
+}
+
+#[aoc_generator(day4)]
+pub fn input_generator(input: &str) -> Vec<ShiftRecord> {
+    let mut events: Vec<(DateTime<FixedOffset>, EventKind)> = input
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| parse_event(l).unwrap())
+        .collect();
+
+    // sort on the parsed timestamp rather than the raw text, so differently
+    // formatted (or zoned) timestamps still end up in chronological order.
+    events.sort_unstable();
+
+    events
+        .into_iter()
+        .peekable()
+        .batching(|it| match it.next() {
+            None => None,
+            Some((dt, EventKind::BeginShift(guard))) => {
+                let mut shift = ShiftRecord {
+                    guard,
+                    events: vec![Event::Start(dt)],
+                };
+                shift.events.extend(
+                    it.peeking_take_while(|(_, kind)| !kind.begins_shift())
+                        .map(|(dt, kind)| kind.into_event(dt)),
+                );
+                Some(shift)
+            }
+            Some((dt, kind)) => panic!("unexpected {:?} event at {} before any shift", kind, dt),
+        })
         .collect()
 }
 
@@ -170,3 +464,104 @@ pub fn solve_part2(input: &[ShiftRecord]) -> u32 {
 
     minute * guard
 }
+
+#[test]
+fn test_export_roundtrip() {
+    let shifts = input_generator(
+        "[1518-11-01 00:00] Guard #10 begins shift\n\
+         [1518-11-01 00:05] falls asleep\n\
+         [1518-11-01 00:25] wakes up\n\
+         [1518-11-01 00:30] falls asleep\n\
+         [1518-11-01 00:55] wakes up\n\
+         [1518-11-01 23:58] Guard #99 begins shift\n\
+         [1518-11-03 00:05] Guard #10 begins shift",
+    );
+
+    let csv = to_csv(&shifts).unwrap();
+    assert_eq!(csv.lines().count(), 5);
+    assert_eq!(from_csv(&csv).unwrap(), shifts);
+
+    let json = to_json_lines(&shifts).unwrap();
+    assert_eq!(json.lines().count(), 3);
+    // chrono writes a zero offset as "+00:00" or "Z" depending on its version
+    assert_eq!(
+        json.lines().next().unwrap().replace("+00:00", "Z"),
+        r#"{"guard":10,"start":"1518-11-01T00:00:00Z","intervals":[{"sleep":"1518-11-01T00:05:00Z","wake":"1518-11-01T00:25:00Z"},{"sleep":"1518-11-01T00:30:00Z","wake":"1518-11-01T00:55:00Z"}]}"#
+    );
+    assert_eq!(from_json_lines(&json).unwrap(), shifts);
+}
+
+#[test]
+fn test_import_errors() {
+    let record = |intervals: &str| {
+        format!(
+            r#"{{"guard":1,"start":"1518-11-01T00:00:00Z","intervals":[{}]}}"#,
+            intervals
+        )
+    };
+    let nap = |sleep: u32, wake: u32| {
+        format!(
+            r#"{{"sleep":"1518-11-01T00:{:02}:00Z","wake":"1518-11-01T00:{:02}:00Z"}}"#,
+            sleep, wake
+        )
+    };
+
+    assert!(from_json_lines(&record("")).is_ok());
+    assert!(from_json_lines(&record(&nap(10, 20))).is_ok());
+    assert!(from_json_lines(r#"{"guard":1,"events":[]}"#).is_err());
+    assert!(from_json_lines(&record(&nap(20, 10))).is_err());
+    assert!(from_json_lines(&record(&format!("{},{}", nap(10, 30), nap(20, 40)))).is_err());
+    assert!(from_json_lines(&record(&format!("{},{}", nap(30, 40), nap(10, 20)))).is_err());
+
+    assert!(from_csv("guard,start,sleep,wake\n1,1518-11-01T00:05:00Z,1518-11-01T00:00:00Z,1518-11-01T00:10:00Z\n").is_err());
+}
+
+#[test]
+fn test_mixed_timestamp_formats() {
+    let shifts = input_generator(
+        "[1518-11-01 00:25:00] wakes up\n\
+         [1518/11/01 00:05] falls asleep\n\
+         [1518-11-01T23:58] Guard #99 begins shift\n\
+         [1518-11-01 01:00 +0100] Guard #10 begins shift\n\
+         [1518-11-02T00:40:00Z] falls asleep\n\
+         [1518-11-02 00:50] wakes up",
+    );
+
+    assert_eq!(shifts.len(), 2);
+    assert_eq!(shifts[0].guard, 10);
+    assert_eq!(shifts[0].sleep_ranges(), vec![5..25]);
+    assert_eq!(shifts[1].guard, 99);
+    assert_eq!(shifts[1].sleep_ranges(), vec![40..50]);
+}
+
+#[test]
+fn test_half_hour_offset() {
+    let shifts = input_generator(
+        "[1518-11-01 00:00 +0530] Guard #10 begins shift\n\
+         [1518-11-01 00:25 +0530] falls asleep\n\
+         [1518-11-01 00:45 +0530] wakes up\n\
+         [1518-11-02 00:00 +0530] Guard #10 begins shift\n\
+         [1518-11-02 00:30 +0530] falls asleep\n\
+         [1518-11-02 00:31 +0530] wakes up",
+    );
+
+    assert_eq!(shifts[0].minutes_slept(), 20);
+    assert_eq!(shifts[0].sleep_ranges(), vec![25..45]);
+    assert_eq!(solve_part1(&shifts), 10 * 30);
+
+    let csv = to_csv(&shifts).unwrap();
+    assert!(csv.contains("1518-11-01T00:25:00+05:30"));
+    assert_eq!(from_csv(&csv).unwrap(), shifts);
+    assert_eq!(
+        from_json_lines(&to_json_lines(&shifts).unwrap()).unwrap()[0].sleep_ranges(),
+        vec![25..45]
+    );
+}
+
+#[test]
+fn test_parse_event_errors() {
+    assert!(parse_event("1518-11-01 00:05 falls asleep").is_err());
+    assert!(parse_event("[1518-11-01 00:05 falls asleep").is_err());
+    assert!(parse_event("[11/01/1518 00:05] falls asleep").is_err());
+    assert!(parse_event("[1518-11-01 00:05] dozes off").is_err());
+}
@@ -1,4 +1,7 @@
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
 use rayon::prelude::*;
+use std::io::{self, Read};
 
 #[aoc_generator(day5)]
 pub fn input_generator(input: &str) -> String {
@@ -6,21 +9,7 @@ pub fn input_generator(input: &str) -> String {
 }
 
 fn reject_adjecent_pairs(chars: impl Iterator<Item = char>) -> String {
-    chars
-        .fold(Vec::new(), |mut nv: Vec<char>, b| {
-            if let Some(a) = nv.last() {
-                if *a != b && a.eq_ignore_ascii_case(&b) {
-                    nv.pop();
-                } else {
-                    nv.push(b);
-                }
-            } else {
-                nv.push(b);
-            }
-            nv
-        })
-        .iter()
-        .collect::<String>()
+    reduce_with(chars, &AsciiCase).iter().collect::<String>()
 }
 
 #[test]
@@ -34,11 +23,55 @@ fn test_1() {
     assert_eq!(reject_adjecent_pairs("aabAAB".chars()), "aabAAB".to_owned());
 }
 
+/// Whether two adjacent units annihilate: the same letter in opposite cases.
+fn reacts(a: u8, b: u8) -> bool {
+    a != b && a.eq_ignore_ascii_case(&b)
+}
+
+/// Reduces the polymer in place, using the front of the buffer as the stack of
+/// surviving units.
+fn reduce_in_place(polymer: &mut Vec<u8>) {
+    let mut len = 0;
+
+    for i in 0..polymer.len() {
+        let b = polymer[i];
+        if len > 0 && reacts(polymer[len - 1], b) {
+            len -= 1;
+        } else {
+            polymer[len] = b;
+            len += 1;
+        }
+    }
+
+    polymer.truncate(len);
+}
+
+#[test]
+fn test_reduce_in_place() {
+    for (polymer, reduced) in &[
+        ("dabAcCaCBAcCcaDA", "dabCBAcaDA"),
+        ("abBA", ""),
+        ("abAB", "abAB"),
+        ("aabAAB", "aabAAB"),
+    ] {
+        let mut units = polymer.as_bytes().to_vec();
+        reduce_in_place(&mut units);
+        assert_eq!(units, reduced.as_bytes());
+    }
+}
+
 #[aoc(day5, part1)]
 pub fn solve_part1(input: &str) -> usize {
     reject_adjecent_pairs(input.chars()).len()
 }
 
+#[aoc(day5, part1, bytes)]
+pub fn solve_part1_bytes(input: &str) -> usize {
+    let mut units = input.as_bytes().to_vec();
+    reduce_in_place(&mut units);
+    units.len()
+}
+
 const A_Z: [char; 26] = [
     'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
     't', 'u', 'v', 'w', 'x', 'y', 'z',
@@ -48,9 +81,401 @@ const A_Z: [char; 26] = [
 pub fn solve_part2(input: &str) -> usize {
     A_Z.par_iter()
         .map(|p| {
-            let i = input.chars().filter(|c| !c.eq_ignore_ascii_case(&p));
+            let i = input.chars().filter(|c| !c.eq_ignore_ascii_case(p));
             reject_adjecent_pairs(i).len()
         })
         .min()
         .unwrap()
 }
+
+#[aoc(day5, part2, prereduce)]
+pub fn solve_part2_prereduce(input: &str) -> usize {
+    // removing a unit type can't stop any of the reactions which already
+    // happened, so reduce once up front and work from the shorter polymer.
+    let mut reducer = PolymerReducer::new();
+    reducer.push(input.as_bytes());
+    reducer.best_removal_len()
+}
+
+#[test]
+fn test_sample() {
+    let input = input_generator("dabAcCaCBAcCcaDA\n");
+    assert_eq!(solve_part1(&input), 10);
+    assert_eq!(solve_part1_bytes(&input), 10);
+    assert_eq!(solve_part2(&input), 4);
+    assert_eq!(solve_part2_prereduce(&input), 4);
+}
+
+/// Decides which adjacent units annihilate each other, and which class of units
+/// part2 removes together.
+pub trait ReactionRules {
+    fn reacts(&self, a: char, b: char) -> bool;
+
+    /// The removable class `unit` belongs to, or `None` if it may never be
+    /// removed.
+    fn class_of(&self, unit: char) -> Option<char>;
+}
+
+/// The 2018 rules: the same ASCII letter in opposite cases reacts, and a unit
+/// type is removed in both of its cases.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AsciiCase;
+
+impl ReactionRules for AsciiCase {
+    fn reacts(&self, a: char, b: char) -> bool {
+        a.is_ascii() && b.is_ascii() && reacts(a as u8, b as u8)
+    }
+
+    fn class_of(&self, unit: char) -> Option<char> {
+        if unit.is_ascii_alphabetic() {
+            Some(unit.to_ascii_lowercase())
+        } else {
+            None
+        }
+    }
+}
+
+/// Like `AsciiCase`, but any pair of Unicode upper/lowercase letters reacts.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct UnicodeCase;
+
+impl ReactionRules for UnicodeCase {
+    fn reacts(&self, a: char, b: char) -> bool {
+        a != b && (a.is_uppercase() != b.is_uppercase()) && a.to_lowercase().eq(b.to_lowercase())
+    }
+
+    fn class_of(&self, unit: char) -> Option<char> {
+        if unit.is_alphabetic() {
+            unit.to_lowercase().next()
+        } else {
+            None
+        }
+    }
+}
+
+/// An explicit table of reacting pairs. Reactions are symmetric, so adding
+/// `('x', 'y')` also makes `yx` react. Unless removable classes are given with
+/// `removable`, each pair added forms its own class named after its first unit.
+#[derive(Clone, Debug, Default)]
+pub struct PairTable {
+    pairs: HashSet<(char, char)>,
+    classes: HashMap<char, char>,
+}
+
+impl PairTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pair(mut self, a: char, b: char) -> Self {
+        self.pairs.insert((a, b));
+        self.pairs.insert((b, a));
+        self.classes.entry(a).or_insert(a);
+        self.classes.entry(b).or_insert(a);
+        self
+    }
+
+    /// Puts `units` into the removable class `class`, replacing whichever
+    /// class they were in before.
+    pub fn removable(mut self, class: char, units: &[char]) -> Self {
+        for &u in units {
+            self.classes.insert(u, class);
+        }
+        self
+    }
+}
+
+impl ReactionRules for PairTable {
+    fn reacts(&self, a: char, b: char) -> bool {
+        self.pairs.contains(&(a, b))
+    }
+
+    fn class_of(&self, unit: char) -> Option<char> {
+        self.classes.get(&unit).cloned()
+    }
+}
+
+fn reduce_with<R: ReactionRules>(chars: impl Iterator<Item = char>, rules: &R) -> Vec<char> {
+    chars.fold(Vec::new(), |mut nv, b| {
+        match nv.last() {
+            Some(&a) if rules.reacts(a, b) => {
+                nv.pop();
+            }
+            _ => nv.push(b),
+        }
+        nv
+    })
+}
+
+#[derive(Clone, Debug)]
+pub struct Polymer<R = AsciiCase> {
+    units: Vec<char>,
+    rules: R,
+}
+
+impl Polymer<AsciiCase> {
+    pub fn new(units: &str) -> Self {
+        Polymer::with_rules(units, AsciiCase)
+    }
+}
+
+impl<R: ReactionRules> Polymer<R> {
+    pub fn with_rules(units: &str, rules: R) -> Self {
+        Polymer {
+            units: units.chars().collect(),
+            rules,
+        }
+    }
+
+    pub fn reduce(&self) -> String {
+        reduce_with(self.units.iter().cloned(), &self.rules)
+            .into_iter()
+            .collect()
+    }
+
+    pub fn reduced_len(&self) -> usize {
+        reduce_with(self.units.iter().cloned(), &self.rules).len()
+    }
+
+    /// The distinct removable classes present in the polymer, in sorted order.
+    pub fn classes(&self) -> Vec<char> {
+        self.units
+            .iter()
+            .filter_map(|&u| self.rules.class_of(u))
+            .unique()
+            .sorted()
+    }
+
+    /// Reduced length of the polymer after removing every unit in `class`.
+    pub fn reduced_len_without(&self, class: char) -> usize {
+        let units = self
+            .units
+            .iter()
+            .cloned()
+            .filter(|&u| self.rules.class_of(u) != Some(class));
+        reduce_with(units, &self.rules).len()
+    }
+}
+
+impl<R: ReactionRules + Sync> Polymer<R> {
+    /// Finds the class whose removal gives the shortest reduced polymer,
+    /// returning it along with that length.
+    pub fn best_removal(&self) -> Option<(char, usize)> {
+        self.classes()
+            .into_par_iter()
+            .map(|class| (class, self.reduced_len_without(class)))
+            .min_by_key(|&(class, len)| (len, class))
+    }
+}
+
+#[aoc(day5, part1, rules)]
+pub fn solve_part1_rules(input: &str) -> usize {
+    Polymer::new(input).reduced_len()
+}
+
+#[aoc(day5, part2, rules)]
+pub fn solve_part2_rules(input: &str) -> usize {
+    let polymer = Polymer::new(input);
+    // with nothing removable, the best is to remove nothing, as part2 does
+    polymer
+        .best_removal()
+        .map_or_else(|| polymer.reduced_len(), |(_class, len)| len)
+}
+
+#[test]
+fn test_default_rules() {
+    let polymer = Polymer::new("dabAcCaCBAcCcaDA");
+    assert_eq!(polymer.reduce(), "dabCBAcaDA");
+    assert_eq!(polymer.classes(), vec!['a', 'b', 'c', 'd']);
+    assert_eq!(polymer.reduced_len_without('c'), 4);
+    assert_eq!(polymer.best_removal(), Some(('c', 4)));
+
+    // nothing to remove: same answer as the baseline part2
+    for input in &["", "12-3"] {
+        assert_eq!(Polymer::new(input).best_removal(), None);
+        assert_eq!(solve_part2_rules(input), solve_part2(input));
+    }
+}
+
+#[test]
+fn test_custom_rules() {
+    let polymer = Polymer::with_rules("xÄäyŒœz", UnicodeCase);
+    assert_eq!(polymer.reduce(), "xyz");
+
+    let rules = PairTable::new()
+        .pair('x', 'y')
+        .pair('1', '2')
+        .removable('d', &['1', '2']);
+    let polymer = Polymer::with_rules("ayxb1x2yx", rules);
+    assert_eq!(polymer.reduce(), "ab1x2");
+    assert_eq!(polymer.classes(), vec!['d', 'x']);
+    assert_eq!(polymer.reduced_len_without('d'), 3);
+    assert_eq!(polymer.reduced_len_without('x'), 2);
+    assert_eq!(polymer.best_removal(), Some(('x', 2)));
+}
+
+/// Incrementally reduces a polymer fed to it in arbitrarily sized chunks,
+/// using the 2018 reaction rules. Only the reduced stack is kept, so the input
+/// never needs to be held in memory in full. Whitespace is ignored.
+#[derive(Clone, Debug, Default)]
+pub struct PolymerReducer {
+    stack: Vec<u8>,
+}
+
+impl PolymerReducer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) {
+        for &b in chunk {
+            if b.is_ascii_whitespace() {
+                continue;
+            }
+            match self.stack.last() {
+                Some(&a) if reacts(a, b) => {
+                    self.stack.pop();
+                }
+                _ => self.stack.push(b),
+            }
+        }
+    }
+
+    /// Feeds everything from `rdr` through the reducer, returning the number of
+    /// bytes read.
+    pub fn read_from(&mut self, mut rdr: impl Read) -> io::Result<usize> {
+        let mut buf = [0u8; 64 * 1024];
+        let mut total = 0;
+
+        loop {
+            match rdr.read(&mut buf) {
+                Ok(0) => return Ok(total),
+                Ok(n) => {
+                    self.push(&buf[..n]);
+                    total += n;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Length of the polymer reduced so far.
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.stack
+    }
+
+    /// The part2 answer for everything pushed so far, computed from the
+    /// already-reduced stack.
+    pub fn best_removal_len(&self) -> usize {
+        A_Z.par_iter()
+            .map(|&p| {
+                let mut units: Vec<u8> = self
+                    .stack
+                    .iter()
+                    .cloned()
+                    .filter(|b| !b.eq_ignore_ascii_case(&(p as u8)))
+                    .collect();
+                reduce_in_place(&mut units);
+                units.len()
+            })
+            .min()
+            .unwrap()
+    }
+}
+
+#[aoc(day5, part1, stream)]
+pub fn solve_part1_stream(input: &str) -> usize {
+    let mut reducer = PolymerReducer::new();
+    reducer.read_from(input.as_bytes()).unwrap();
+    reducer.len()
+}
+
+#[test]
+fn test_reducer_chunks() {
+    let mut reducer = PolymerReducer::new();
+    reducer.push(b"dabA");
+    assert_eq!(reducer.len(), 4);
+    reducer.push(b"cCaC");
+    assert_eq!(reducer.as_bytes(), b"dabC");
+    reducer.push(b"BAcCcaDA\n");
+    assert_eq!(reducer.as_bytes(), b"dabCBAcaDA");
+    assert_eq!(reducer.best_removal_len(), 4);
+
+    let mut reducer = PolymerReducer::new();
+    for b in b"abBA".chunks(1) {
+        reducer.push(b);
+    }
+    assert!(reducer.is_empty());
+
+    let mut reducer = PolymerReducer::new();
+    assert_eq!(reducer.read_from(&b"dabAcCaCBAcCcaDA\n"[..]).unwrap(), 17);
+    assert_eq!(reducer.len(), 10);
+}
+
+/// Concatenates two already-reduced polymers, cancelling units across the
+/// boundary. Neither side can react internally, so only the units meeting at
+/// the join need checking.
+fn merge_reduced(mut left: Vec<u8>, right: Vec<u8>) -> Vec<u8> {
+    let mut skip = 0;
+
+    while let (Some(&a), Some(&b)) = (left.last(), right.get(skip)) {
+        if reacts(a, b) {
+            left.pop();
+            skip += 1;
+        } else {
+            break;
+        }
+    }
+
+    left.extend_from_slice(&right[skip..]);
+    left
+}
+
+/// Reduces `chunk_size` pieces of the polymer in parallel, then merges the
+/// reduced pieces back together.
+fn reduce_parallel(units: &[u8], chunk_size: usize) -> Vec<u8> {
+    units
+        .par_chunks(chunk_size.max(1))
+        .map(|chunk| {
+            let mut reduced = chunk.to_vec();
+            reduce_in_place(&mut reduced);
+            reduced
+        })
+        .reduce(Vec::new, merge_reduced)
+}
+
+#[aoc(day5, part1, parallel)]
+pub fn solve_part1_parallel(input: &str) -> usize {
+    let chunk_size = input.len() / (rayon::current_num_threads() * 4) + 1;
+    reduce_parallel(input.as_bytes(), chunk_size).len()
+}
+
+#[test]
+fn test_reduce_parallel() {
+    let mut next = crate::testutil::xorshift(0x9e37_79b9);
+
+    // a small alphabet makes long cascades of reactions likely.
+    let alphabet = b"aAbBcC";
+
+    for _ in 0..200 {
+        let len = (next() % 300) as usize;
+        let polymer: Vec<u8> = (0..len)
+            .map(|_| alphabet[next() as usize % alphabet.len()])
+            .collect();
+        let expected = reject_adjecent_pairs(polymer.iter().map(|&b| b as char));
+
+        for &chunk_size in &[1, 2, 3, 7, 64, 1000] {
+            let reduced = reduce_parallel(&polymer, chunk_size);
+            assert_eq!(String::from_utf8(reduced).unwrap(), expected);
+        }
+    }
+}
@@ -1,6 +1,8 @@
-use failure::Error;
+use failure::{bail, err_msg, Error};
+use fxhash::FxHashSet;
 use itertools::Itertools;
 use rayon::prelude::*;
+use std::collections::VecDeque;
 use std::str::FromStr;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -47,11 +49,176 @@ fn test_sample() {
     assert_eq!(solve_part1(&points).unwrap(), 17);
 }
 
+#[test]
+fn test_infinite_points() {
+    // label every cell of a window much wider than the bounding box: regions
+    // reaching its edge are infinite, and the rest must lie within the box.
+    let check = |points: &[Point]| {
+        let (tl, br) = xy_points_minmax(points);
+        let (x0, x1, y0, y1) = (tl.x - 40, br.x + 40, tl.y - 40, br.y + 40);
+        let labels: Vec<(Point, (i32, i32))> = (x0..=x1)
+            .cartesian_product(y0..=y1)
+            .filter_map(|(x, y)| nearest_point(x, y, points).map(|pt| (pt, (x, y))))
+            .collect();
+
+        let expected: FxHashSet<Point> = labels
+            .iter()
+            .filter(|(_pt, (x, y))| *x == x0 || *x == x1 || *y == y0 || *y == y1)
+            .map(|(pt, _cell)| *pt)
+            .collect();
+        assert_eq!(infinite_points(points, tl, br), expected, "{:?}", points);
+
+        for (pt, (x, y)) in labels {
+            if !expected.contains(&pt) {
+                assert!(tl.x <= x && x <= br.x && tl.y <= y && y <= br.y);
+            }
+        }
+    };
+
+    check(&input_generator("1, 1\n1, 6\n8, 3\n3, 4\n5, 5\n8, 9").unwrap());
+    check(&input_generator("1, 1\n9, 1\n1, 9\n9, 9\n5, 5\n3, 5").unwrap());
+    check(&input_generator("1, 1\n4, 1\n9, 1").unwrap());
+
+    let mut next = crate::testutil::xorshift(0x2545_f491);
+    for _ in 0..20 {
+        let n = 2 + next() % 8;
+        let points: Vec<Point> = (0..n)
+            .map(|_| Point {
+                x: (next() % 20) as i32,
+                y: (next() % 20) as i32,
+            })
+            .collect();
+        check(&points);
+    }
+
+    // collinear points are all infinite, and there's no finite region to
+    // report.
+    assert!(solve_part1(&input_generator("1, 1\n4, 1\n9, 1").unwrap()).is_err());
+}
+
 #[aoc_generator(day6)]
 pub fn input_generator(input: &str) -> Result<Vec<Point>, Error> {
     input.lines().map(|l| l.parse()).collect()
 }
 
+/// A distance function over grid cells, for labeling cells by their nearest
+/// input point.
+pub trait Metric: Sync {
+    fn dist(&self, pt: &Point, x: i32, y: i32) -> u64;
+
+    /// How far past the input points' bounding box cells need to be labeled
+    /// so that any region owning a cell on the edge of that window is
+    /// infinite, and every finite region lies inside it.
+    fn margin(&self, _width: i32, _height: i32) -> i32 {
+        1
+    }
+}
+
+/// Stepping straight away from the bounding box moves a cell exactly one
+/// unit further from every input point, so the one-cell ring around the box is
+/// enough to spot infinite regions (see `infinite_points`).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Manhattan;
+
+impl Metric for Manhattan {
+    fn dist(&self, pt: &Point, x: i32, y: i32) -> u64 {
+        u64::from(pt.mh_dist_to(x, y))
+    }
+}
+
+/// Manhattan distance with separate costs per unit of x and y; the one-cell
+/// ring works here for the same reason it does for `Manhattan`. Both costs
+/// must be positive: with a free axis, stepping away from the box along it
+/// doesn't move a cell any further from the input points.
+#[derive(Copy, Clone, Debug)]
+pub struct WeightedManhattan {
+    x: u64,
+    y: u64,
+}
+
+impl WeightedManhattan {
+    pub fn new(x: u64, y: u64) -> Result<Self, Error> {
+        if x == 0 || y == 0 {
+            bail!("weights must be positive, got x={} y={}", x, y);
+        }
+        Ok(WeightedManhattan { x, y })
+    }
+}
+
+impl Metric for WeightedManhattan {
+    fn dist(&self, pt: &Point, x: i32, y: i32) -> u64 {
+        self.x * u64::from((pt.x - x).unsigned_abs())
+            + self.y * u64::from((pt.y - y).unsigned_abs())
+    }
+}
+
+/// Chebyshev distance is manhattan distance on the grid rotated by 45
+/// degrees, so the argument used for `Manhattan` applies to diagonal steps
+/// away from the rotated bounding box. That box pokes out of the axis-aligned
+/// one by at most half its larger side.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Chebyshev;
+
+impl Metric for Chebyshev {
+    fn dist(&self, pt: &Point, x: i32, y: i32) -> u64 {
+        u64::from((pt.x - x).unsigned_abs().max((pt.y - y).unsigned_abs()))
+    }
+
+    fn margin(&self, width: i32, height: i32) -> i32 {
+        width.max(height) / 2 + 2
+    }
+}
+
+/// Squared euclidean distance, which labels cells the same way euclidean
+/// distance does without needing floating point. No finite margin is exact
+/// for every input here (nearly-collinear points can have regions reaching
+/// arbitrarily far out), so this pads the bounding box by its own size on
+/// every side and treats regions reaching past that as infinite.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EuclideanSquared;
+
+impl Metric for EuclideanSquared {
+    fn dist(&self, pt: &Point, x: i32, y: i32) -> u64 {
+        let (dx, dy) = ((pt.x - x) as i64, (pt.y - y) as i64);
+        (dx * dx + dy * dy) as u64
+    }
+
+    fn margin(&self, width: i32, height: i32) -> i32 {
+        width.max(height) + 1
+    }
+}
+
+/// Finds the input point nearest to `(x, y)`, or `None` if there's a tie.
+fn nearest_point_by<M: Metric>(x: i32, y: i32, points: &[Point], metric: &M) -> Option<Point> {
+    // for each point in the bounding box, find the distance to each input
+    // point.
+    let mut dists: Vec<(Point, u64)> = points
+        .iter()
+        .map(|pt| (*pt, metric.dist(pt, x, y)))
+        .collect();
+
+    dists.sort_unstable_by_key(|(_k, dist)| *dist);
+
+    // only collect points which have a unique nearest-input-point
+    match (dists.first(), dists.get(1)) {
+        (Some((pa, _dist)), None) => Some(*pa),
+        (Some((pa, da)), Some((_pb, db))) => {
+            if da < db {
+                Some(*pa)
+            } else {
+                // current coordinate has a tie for nearest-input-point, so
+                // it doesn't count.
+                None
+            }
+        }
+        _ => panic!("failed to process grid location ({}, {})", x, y),
+    }
+}
+
+fn nearest_point(x: i32, y: i32, points: &[Point]) -> Option<Point> {
+    nearest_point_by(x, y, points, &Manhattan)
+}
+
 fn mh_dist_to_points(
     grid_iter: impl Iterator<Item = (i32, i32)>,
     points: &[Point],
@@ -60,32 +227,34 @@ fn mh_dist_to_points(
 
     grid_points
         .par_iter()
-        .filter_map(|&(x, y)| {
-            // for each point in the bounding box, find the distance to each input
-            // point.
-            let mut dists: Vec<(Point, u32)> = points
-                .iter()
-                .enumerate()
-                .map(|(_idx, pt)| (*pt, pt.mh_dist_to(x, y)))
-                .collect();
-
-            dists.par_sort_unstable_by_key(|(_k, dist)| *dist);
-
-            // only collect points which have a unique nearest-input-point
-            match (dists.get(0), dists.get(1)) {
-                (Some((pa, _dist)), None) => Some((*pa, Point { x, y })),
-                (Some((pa, da)), Some((_pb, db))) => {
-                    if da < db {
-                        Some((*pa, Point { x, y }))
-                    } else {
-                        // current coordinate has a tie for nearest-input-point, so
-                        // it doesn't count.
-                        None
-                    }
-                }
-                _ => panic!("failed to process grid location ({}, {})", x, y),
-            }
-        })
+        .filter_map(|&(x, y)| nearest_point(x, y, points).map(|pa| (pa, Point { x, y })))
+        .collect()
+}
+
+/// Iterates over the ring of cells just outside the box spanned by `tl` and
+/// `br`.
+fn border_ring(tl: Point, br: Point) -> impl Iterator<Item = (i32, i32)> {
+    let (x0, x1, y0, y1) = (tl.x - 1, br.x + 1, tl.y - 1, br.y + 1);
+
+    (x0..=x1)
+        .flat_map(move |x| vec![(x, y0), (x, y1)])
+        .chain((tl.y..=br.y).flat_map(move |y| vec![(x0, y), (x1, y)]))
+}
+
+/// Finds the input points whose regions are infinite.
+///
+/// Stepping directly away from the bounding box moves a cell exactly one unit
+/// further from *every* input point, so each cell outside the box has the same
+/// nearest point as the cell it projects onto in the ring just outside the
+/// box. A region is therefore infinite exactly when it owns a ring cell, and
+/// every finite region lies entirely within the box.
+///
+/// The same argument shows this agrees with checking the cells on the box's
+/// own edge: each edge cell and the ring cell just outside it differ by exactly
+/// one unit from every input point, so they share a nearest point.
+fn infinite_points(points: &[Point], tl: Point, br: Point) -> FxHashSet<Point> {
+    border_ring(tl, br)
+        .filter_map(|(x, y)| nearest_point(x, y, points))
         .collect()
 }
 
@@ -116,41 +285,567 @@ pub fn solve_part1(points: &[Point]) -> Result<usize, Error> {
 
     let distances = mh_dist_to_points(x_range.cartesian_product(y_range), points);
     let nearest_by_point = distances.into_iter().into_group_map();
+    let infinite = infinite_points(points, top_left, bot_right);
 
-    Ok(nearest_by_point
-        .values()
-        .filter_map(|nearby| {
-            if nearby.iter().all(|pt| {
-                pt.x != top_left.x
-                    && pt.x != bot_right.x
-                    && pt.y != top_left.y
-                    && pt.y != bot_right.y
-            }) {
-                Some(nearby.len())
-            } else {
-                None
+    nearest_by_point
+        .iter()
+        .filter(|(pt, _nearby)| !infinite.contains(pt))
+        .map(|(_pt, nearby)| nearby.len())
+        .max()
+        .ok_or_else(|| err_msg("every region is infinite"))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Label {
+    Unvisited,
+    Nearest(usize),
+    Tie,
+}
+
+/// Labels every cell in the bounding box with its nearest input point (by
+/// index), using a breadth-first search started from all the input points at
+/// once. Cells are labeled in order of distance, so each one is finalized by
+/// the time it's expanded; a cell reached at the same distance from different
+/// points (or from a tied cell) becomes a tie.
+///
+/// Shortest manhattan paths between cells in the box never need to leave it,
+/// so the search doesn't either. The result is indexed by `y * width + x`,
+/// relative to `tl`.
+fn bfs_nearest_labels(points: &[Point], tl: Point, br: Point) -> Vec<Label> {
+    let width = (br.x - tl.x + 1) as usize;
+    let height = (br.y - tl.y + 1) as usize;
+    let mut labels = vec![Label::Unvisited; width * height];
+    let mut dists = vec![0u32; width * height];
+    let mut queue = VecDeque::new();
+
+    for (idx, pt) in points.iter().enumerate() {
+        let cell = (pt.y - tl.y) as usize * width + (pt.x - tl.x) as usize;
+        labels[cell] = match labels[cell] {
+            Label::Unvisited => {
+                queue.push_back(cell);
+                Label::Nearest(idx)
             }
-        })
+            _ => Label::Tie,
+        };
+    }
+
+    while let Some(cell) = queue.pop_front() {
+        let (x, y) = (cell % width, cell / width);
+        let label = labels[cell];
+        let dist = dists[cell] + 1;
+
+        let neighbors = [
+            (x > 0, cell.wrapping_sub(1)),
+            (x + 1 < width, cell + 1),
+            (y > 0, cell.wrapping_sub(width)),
+            (y + 1 < height, cell + width),
+        ];
+
+        for &(in_bounds, next) in neighbors.iter() {
+            if !in_bounds {
+                continue;
+            }
+            match labels[next] {
+                Label::Unvisited => {
+                    labels[next] = label;
+                    dists[next] = dist;
+                    queue.push_back(next);
+                }
+                Label::Nearest(other) if dists[next] == dist && label != Label::Nearest(other) => {
+                    labels[next] = Label::Tie;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    labels
+}
+
+#[aoc(day6, part1, bfs)]
+pub fn solve_part1_bfs(points: &[Point]) -> Result<usize, Error> {
+    let (top_left, bot_right) = xy_points_minmax(points);
+    let infinite = infinite_points(points, top_left, bot_right);
+    let mut areas = vec![0; points.len()];
+
+    for label in bfs_nearest_labels(points, top_left, bot_right) {
+        if let Label::Nearest(idx) = label {
+            areas[idx] += 1;
+        }
+    }
+
+    areas
+        .into_iter()
+        .zip(points)
+        .filter(|(_area, pt)| !infinite.contains(pt))
+        .map(|(area, _pt)| area)
         .max()
-        .unwrap())
+        .ok_or_else(|| err_msg("every region is infinite"))
 }
 
-fn sum_mh_dist_to_point(x: i32, y: i32, points: &[Point]) -> u32 {
-    points.iter().map(|pt| pt.mh_dist_to(x, y)).sum()
+#[test]
+fn test_bfs_labels() {
+    let points = input_generator("1, 1\n1, 6\n8, 3\n3, 4\n5, 5\n8, 9").unwrap();
+    assert_eq!(solve_part1_bfs(&points).unwrap(), 17);
+
+    // the flood fill has to agree with the brute-force labeling everywhere,
+    // including on ties and duplicated points.
+    let points = input_generator("0, 0\n4, 0\n2, 3\n2, 3\n7, 7\n3, 9\n9, 2").unwrap();
+    let (tl, br) = xy_points_minmax(&points);
+    let labels = bfs_nearest_labels(&points, tl, br);
+    let width = (br.x - tl.x + 1) as usize;
+
+    for (cell, label) in labels.into_iter().enumerate() {
+        let (x, y) = ((cell % width) as i32 + tl.x, (cell / width) as i32 + tl.y);
+        let expected = match nearest_point(x, y, &points) {
+            Some(pt) if pt != (Point { x: 2, y: 3 }) => {
+                Label::Nearest(points.iter().position(|&p| p == pt).unwrap())
+            }
+            _ => Label::Tie,
+        };
+        assert_eq!(label, expected, "mismatch at ({}, {})", x, y);
+    }
 }
 
-#[aoc(day6, part2)]
-pub fn solve_part2(points: &[Point]) -> usize {
+/// Size of the largest finite region under `metric`, as in part1.
+///
+/// Regions are classified exactly for `Manhattan`, `WeightedManhattan` and
+/// `Chebyshev`. `EuclideanSquared` only has a heuristic margin, so under it a
+/// region is treated as infinite when it reaches past that margin; a long,
+/// thin region may be misclassified either way, and the result isn't
+/// guaranteed.
+pub fn largest_finite_area<M: Metric>(points: &[Point], metric: &M) -> Result<usize, Error> {
     let (tl, br) = xy_points_minmax(points);
+    let margin = metric.margin(br.x - tl.x + 1, br.y - tl.y + 1);
+    let (x0, x1, y0, y1) = (tl.x - margin, br.x + margin, tl.y - margin, br.y + margin);
+
+    let labels: Vec<(Point, (i32, i32))> = (x0..=x1)
+        .cartesian_product(y0..=y1)
+        .collect::<Vec<_>>()
+        .par_iter()
+        .filter_map(|&(x, y)| nearest_point_by(x, y, points, metric).map(|pt| (pt, (x, y))))
+        .collect();
+
+    let infinite: FxHashSet<Point> = labels
+        .iter()
+        .filter(|(_pt, (x, y))| *x == x0 || *x == x1 || *y == y0 || *y == y1)
+        .map(|(pt, _cell)| *pt)
+        .collect();
+
+    labels
+        .into_iter()
+        .filter(|(pt, _cell)| !infinite.contains(pt))
+        .into_group_map()
+        .values()
+        .map(|cells| cells.len())
+        .max()
+        .ok_or_else(|| err_msg("every region is infinite"))
+}
+
+/// Number of cells whose total distance to every input point under `metric` is
+/// below `threshold`, as in part2.
+///
+/// The safe region isn't confined to the bounding box when the threshold is
+/// large, so after the box this scans the successive rings of cells around it,
+/// stopping at the first ring without any safe cells. That's exact for every
+/// metric here: stepping from a cell outside the box towards it never moves
+/// the cell further from any input point, so a safe cell further out would
+/// imply safe cells on every ring in between.
+pub fn safe_region_size<M: Metric>(points: &[Point], metric: &M, threshold: u64) -> usize {
+    let (mut tl, mut br) = xy_points_minmax(points);
+    let is_safe = |&(x, y): &(i32, i32)| {
+        points.iter().map(|pt| metric.dist(pt, x, y)).sum::<u64>() < threshold
+    };
 
+    let mut total = ((tl.x)..=(br.x))
+        .cartesian_product((tl.y)..=(br.y))
+        .filter(is_safe)
+        .count();
+
+    loop {
+        let ring = border_ring(tl, br).filter(is_safe).count();
+        if ring == 0 {
+            return total;
+        }
+
+        total += ring;
+        tl = Point {
+            x: tl.x - 1,
+            y: tl.y - 1,
+        };
+        br = Point {
+            x: br.x + 1,
+            y: br.y + 1,
+        };
+    }
+}
+
+#[aoc(day6, part1, metric)]
+pub fn solve_part1_metric(points: &[Point]) -> Result<usize, Error> {
+    largest_finite_area(points, &Manhattan)
+}
+
+#[test]
+fn test_metrics() {
+    let points = input_generator("1, 1\n1, 6\n8, 3\n3, 4\n5, 5\n8, 9").unwrap();
+
+    assert_eq!(largest_finite_area(&points, &Manhattan).unwrap(), 17);
+    assert_eq!(safe_region_size(&points, &Manhattan, 32), 16);
+
+    let unweighted = WeightedManhattan::new(1, 1).unwrap();
+    assert_eq!(largest_finite_area(&points, &unweighted).unwrap(), 17);
+    assert_eq!(safe_region_size(&points, &unweighted, 32), 16);
+
+    let weighted = WeightedManhattan::new(2, 1).unwrap();
+    assert!(largest_finite_area(&points, &weighted).is_err());
+    assert!(WeightedManhattan::new(0, 1).is_err());
+    assert!(WeightedManhattan::new(1, 0).is_err());
+
+    assert_eq!(largest_finite_area(&points, &Chebyshev).unwrap(), 10);
+    assert_eq!(largest_finite_area(&points, &EuclideanSquared).unwrap(), 16);
+}
+
+/// Total distance below which a cell is part of the part2 safe region.
+pub const SAFE_THRESHOLD: u64 = 10000;
+
+#[aoc(day6, part2)]
+pub fn solve_part2(points: &[Point]) -> usize {
     // evaluate each point on the grid -- if the sum of the manhattan distances
     // to all of the input points is less than 10000, that point is "safe". the
     // solution for part2 is the area which is considered safe.
-    ((tl.x)..=(br.x))
-        .cartesian_product((tl.y)..=(br.y))
-        .filter_map(|(x, y)| match sum_mh_dist_to_point(x, y, points) {
-            0..=9999 => Some(Point { x, y }),
-            _ => None,
+    safe_region_size(points, &Manhattan, SAFE_THRESHOLD)
+}
+
+/// Total manhattan distance along one axis from a set of input coordinates,
+/// computed from the sorted coordinates and their prefix sums.
+struct AxisDistances {
+    coords: Vec<i64>,
+    prefix: Vec<i64>,
+}
+
+impl AxisDistances {
+    fn new(coords: impl Iterator<Item = i32>) -> Self {
+        let coords: Vec<i64> = coords.map(i64::from).sorted();
+        let mut prefix = vec![0];
+        for c in &coords {
+            let last = *prefix.last().unwrap();
+            prefix.push(last + c);
+        }
+
+        AxisDistances { coords, prefix }
+    }
+
+    fn total(&self, c: i64) -> i64 {
+        let n = self.coords.len() as i64;
+        let below = self.coords.partition_point(|&v| v < c);
+        let (k, sum_below) = (below as i64, self.prefix[below]);
+        let sum_above = self.prefix[self.coords.len()] - sum_below;
+
+        (k * c - sum_below) + (sum_above - (n - k) * c)
+    }
+
+    /// The median minimizes the total distance.
+    fn median(&self) -> i64 {
+        self.coords[self.coords.len() / 2]
+    }
+
+    /// Counts the coordinates whose total distance is below `budget`. The total
+    /// is convex, so those form a single run around the median; each end of it
+    /// is found by binary search.
+    fn count_below(&self, budget: i64) -> i64 {
+        let mid = self.median();
+        if self.total(mid) >= budget {
+            return 0;
+        }
+
+        // totals grow by at least one per step away from the median, so the
+        // run can't extend further than `budget` in either direction.
+        let last_below = |dir: i64| {
+            let (mut lo, mut hi) = (0, budget);
+            while lo < hi {
+                let step = (lo + hi + 1) / 2;
+                if self.total(mid + dir * step) < budget {
+                    lo = step;
+                } else {
+                    hi = step - 1;
+                }
+            }
+            lo
+        };
+
+        last_below(-1) + last_below(1) + 1
+    }
+}
+
+/// Manhattan-only part2: the total distance splits into independent x and y
+/// totals, so each row's safe cells are a 1-D count against the sorted x
+/// coordinates. Rows are visited outwards from the median row, until a row has
+/// no safe cells at all.
+pub fn manhattan_safe_region_size(points: &[Point], threshold: u64) -> usize {
+    let xs = AxisDistances::new(points.iter().map(|pt| pt.x));
+    let ys = AxisDistances::new(points.iter().map(|pt| pt.y));
+    let threshold = threshold as i64;
+
+    let row_count = |y: i64| xs.count_below(threshold - ys.total(y));
+    let mid = ys.median();
+    let mut total = row_count(mid);
+
+    if total > 0 {
+        for &dir in &[-1, 1] {
+            let mut y = mid + dir;
+            loop {
+                let count = row_count(y);
+                if count == 0 {
+                    break;
+                }
+                total += count;
+                y += dir;
+            }
+        }
+    }
+
+    total as usize
+}
+
+#[aoc(day6, part2, rows)]
+pub fn solve_part2_rows(points: &[Point]) -> usize {
+    manhattan_safe_region_size(points, SAFE_THRESHOLD)
+}
+
+#[test]
+fn test_safe_region() {
+    let points = input_generator("1, 1\n1, 6\n8, 3\n3, 4\n5, 5\n8, 9").unwrap();
+    assert_eq!(safe_region_size(&points, &Manhattan, 32), 16);
+    assert_eq!(manhattan_safe_region_size(&points, 32), 16);
+
+    // a lone point's safe region is a diamond reaching far outside its
+    // (single cell) bounding box.
+    let points = input_generator("4, 4").unwrap();
+    assert_eq!(safe_region_size(&points, &Manhattan, 10), 181);
+    assert_eq!(manhattan_safe_region_size(&points, 10), 181);
+    assert_eq!(manhattan_safe_region_size(&points, 0), 0);
+
+    let points = input_generator("0, 0\n3, 7\n-2, 5\n9, 1\n4, 4").unwrap();
+    for &threshold in &[0, 20, 30, 45, 100, 400] {
+        assert_eq!(
+            manhattan_safe_region_size(&points, threshold),
+            safe_region_size(&points, &Manhattan, threshold),
+            "threshold {}",
+            threshold
+        );
+    }
+}
+
+/// Options for `render_ascii` and `render_ppm`.
+#[derive(Copy, Clone, Debug)]
+pub struct RenderOptions {
+    /// Cells to draw past the input points' bounding box on every side.
+    pub margin: i32,
+    /// Mark the edges of infinite regions.
+    pub outline_infinite: bool,
+    /// Overlay the part2 safe region for this threshold.
+    pub safe_threshold: Option<u64>,
+    /// Size of each cell in pixels, for `render_ppm`.
+    pub scale: usize,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            margin: 1,
+            outline_infinite: true,
+            safe_threshold: None,
+            scale: 1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Cell {
+    Input(usize),
+    Safe(Option<usize>),
+    Outline(usize),
+    Region(usize),
+    Tie,
+}
+
+/// Classifies every cell of the window described by `opts`, returning the
+/// window width along with the cells in row-major order.
+fn render_cells(points: &[Point], opts: &RenderOptions) -> (usize, Vec<Cell>) {
+    let (tl, br) = xy_points_minmax(points);
+    let infinite = infinite_points(points, tl, br);
+    let (x0, x1) = (tl.x - opts.margin, br.x + opts.margin);
+    let (y0, y1) = (tl.y - opts.margin, br.y + opts.margin);
+    let width = (x1 - x0 + 1) as usize;
+
+    let index_of = |pt: Point| points.iter().position(|&p| p == pt).unwrap();
+    let nearest: Vec<Option<usize>> = (y0..=y1)
+        .cartesian_product(x0..=x1)
+        .collect::<Vec<_>>()
+        .par_iter()
+        .map(|&(y, x)| nearest_point(x, y, points).map(index_of))
+        .collect();
+
+    let cells = nearest
+        .iter()
+        .enumerate()
+        .map(|(cell, &label)| {
+            let (x, y) = ((cell % width) as i32 + x0, (cell / width) as i32 + y0);
+
+            if let Some(idx) = points.iter().position(|&p| p == Point { x, y }) {
+                return Cell::Input(idx);
+            }
+            if let Some(threshold) = opts.safe_threshold {
+                if points
+                    .iter()
+                    .map(|pt| u64::from(pt.mh_dist_to(x, y)))
+                    .sum::<u64>()
+                    < threshold
+                {
+                    return Cell::Safe(label);
+                }
+            }
+
+            let idx = match label {
+                Some(idx) => idx,
+                None => return Cell::Tie,
+            };
+
+            // a cell is on the outline if any of its neighbours (or the edge
+            // of the picture) belongs to something else.
+            let on_edge = x == x0 || x == x1 || y == y0 || y == y1;
+            let differs = || {
+                [cell - 1, cell + 1, cell - width, cell + width]
+                    .iter()
+                    .any(|&n| nearest[n] != label)
+            };
+
+            if opts.outline_infinite && infinite.contains(&points[idx]) && (on_edge || differs()) {
+                Cell::Outline(idx)
+            } else {
+                Cell::Region(idx)
+            }
+        })
+        .collect();
+
+    (width, cells)
+}
+
+/// Draws the regions in the style of the puzzle's example: input points are
+/// uppercase letters, the cells nearest to them the same letter in lowercase,
+/// and ties are `.`. Infinite regions are outlined with `+` and safe cells
+/// are drawn as `#`. Letters repeat after 26 points.
+pub fn render_ascii(points: &[Point], opts: &RenderOptions) -> String {
+    let letter = |idx: usize| (b'a' + (idx % 26) as u8) as char;
+    let (width, cells) = render_cells(points, opts);
+
+    cells
+        .chunks(width)
+        .map(|row| {
+            row.iter()
+                .map(|cell| match *cell {
+                    Cell::Input(idx) => letter(idx).to_ascii_uppercase(),
+                    Cell::Safe(_) => '#',
+                    Cell::Outline(_) => '+',
+                    Cell::Region(idx) => letter(idx),
+                    Cell::Tie => '.',
+                })
+                .collect::<String>()
         })
-        .count()
+        .join("\n")
+}
+
+/// Spreads colors around the hue wheel using the golden angle, so neighbouring
+/// indices get distinct colors.
+fn region_color(idx: usize) -> [u8; 3] {
+    let hue = (idx as f64 * 137.507_764) % 360.0;
+    let x = 1.0 - ((hue / 60.0) % 2.0 - 1.0).abs();
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+
+    // keep the colors light enough for the black input points to stand out.
+    let scale = |v: f64| (96.0 + v * 144.0) as u8;
+    [scale(r), scale(g), scale(b)]
+}
+
+/// Draws the same picture as `render_ascii` as a binary (P6) PPM image. Input
+/// points are black, ties white, infinite region outlines dark gray, and safe
+/// cells a lightened version of their region's color.
+pub fn render_ppm(points: &[Point], opts: &RenderOptions) -> Vec<u8> {
+    let (width, cells) = render_cells(points, opts);
+    let height = cells.len() / width;
+    let scale = opts.scale.max(1);
+
+    let mut ppm = format!("P6\n{} {}\n255\n", width * scale, height * scale).into_bytes();
+
+    for row in cells.chunks(width) {
+        let pixels: Vec<u8> = row
+            .iter()
+            .flat_map(|cell| {
+                let rgb = match *cell {
+                    Cell::Input(_) => [0, 0, 0],
+                    Cell::Safe(Some(idx)) => {
+                        let [r, g, b] = region_color(idx);
+                        [r / 2 + 128, g / 2 + 128, b / 2 + 128]
+                    }
+                    Cell::Safe(None) => [240, 240, 240],
+                    Cell::Outline(_) => [64, 64, 64],
+                    Cell::Region(idx) => region_color(idx),
+                    Cell::Tie => [255, 255, 255],
+                };
+                rgb.iter()
+                    .cloned()
+                    .cycle()
+                    .take(3 * scale)
+                    .collect::<Vec<u8>>()
+            })
+            .collect();
+
+        for _ in 0..scale {
+            ppm.extend_from_slice(&pixels);
+        }
+    }
+
+    ppm
+}
+
+#[test]
+fn test_render_ascii() {
+    let points = input_generator("1, 1\n1, 6\n8, 3\n3, 4\n5, 5\n8, 9").unwrap();
+    let opts = RenderOptions {
+        outline_infinite: false,
+        ..RenderOptions::default()
+    };
+
+    // the puzzle's example, which stops one row short of our margin.
+    let expected = "aaaaa.cccc\n\
+                    aAaaa.cccc\n\
+                    aaaddecccc\n\
+                    aadddeccCc\n\
+                    ..dDdeeccc\n\
+                    bb.deEeecc\n\
+                    bBb.eeee..\n\
+                    bbb.eeefff\n\
+                    bbb.eeffff\n\
+                    bbb.ffffFf";
+    let rendered = render_ascii(&points, &opts);
+    assert_eq!(rendered.lines().take(10).join("\n"), expected);
+
+    let opts = RenderOptions {
+        safe_threshold: Some(32),
+        ..RenderOptions::default()
+    };
+    let rendered = render_ascii(&points, &opts);
+    assert_eq!(rendered.lines().nth(4).unwrap(), "..#D###+c+");
+    assert_eq!(rendered.matches('#').count() + 2, 16);
+
+    let ppm = render_ppm(&points, &RenderOptions { scale: 2, ..opts });
+    assert!(ppm.starts_with(b"P6\n20 22\n255\n"));
+    assert_eq!(ppm.len(), b"P6\n20 22\n255\n".len() + 20 * 22 * 3);
 }
@@ -1,32 +1,97 @@
-use failure::Error;
+use failure::{bail, err_msg, Error};
 use itertools::Itertools;
+use petgraph::algo::{tarjan_scc, toposort};
+use petgraph::graphmap::NodeTrait;
 use petgraph::prelude::*;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fmt;
+
+fn parse_step(word: &str) -> Option<char> {
+    let mut chars = word.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_uppercase() => Some(c),
+        _ => None,
+    }
+}
+
+fn parse_edge(line: &str) -> Result<(char, char, u32), Error> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+
+    let (a, b) = match parts.as_slice() {
+        ["Step", a, "must", "be", "finished", "before", "step", b, "can", "begin."] => {
+            match (parse_step(a), parse_step(b)) {
+                (Some(a), Some(b)) => (a, b),
+                _ => bail!("step names must be single uppercase letters"),
+            }
+        }
+        _ => bail!("expected 'Step X must be finished before step Y can begin.'"),
+    };
+
+    if a == b {
+        bail!("step {} can't depend on itself", a);
+    }
+
+    let weight = u32::from((b as i8 - b'Z' as i8).unsigned_abs());
+    Ok((a, b, weight))
+}
 
 #[aoc_generator(day7)]
-pub fn input_generator(input: &str) -> Vec<(char, char, u32)> {
+pub fn input_generator(input: &str) -> Result<Vec<(char, char, u32)>, Error> {
     input
         .lines()
-        .map(|s| {
-            let parts: Vec<&str> = s.split_whitespace().collect();
-            let a = parts[1].chars().next().unwrap();
-            let b = parts[7].chars().next().unwrap();
-            let weight = (b as i8 - b'Z' as i8).abs() as u32;
-            (a, b, weight)
-        })
+        .enumerate()
+        .filter(|(_, s)| !s.trim().is_empty())
+        .map(|(idx, s)| parse_edge(s).map_err(|e| err_msg(format!("line {}: {}", idx + 1, e))))
         .collect()
 }
 
-#[aoc(day7, part1)]
-pub fn solve_part1(edges: &[(char, char, u32)]) -> Result<String, Error> {
+/// Fails if the step graph has a cycle, naming the steps around it.
+fn check_acyclic<N: NodeTrait + fmt::Display>(graph: &DiGraphMap<N, u32>) -> Result<(), Error> {
+    if let Some(scc) = tarjan_scc(graph)
+        .into_iter()
+        .find(|scc| scc.len() > 1 || graph.contains_edge(scc[0], scc[0]))
+    {
+        // every step in the component leads to another one in it, so walking
+        // from any of them has to come back around to a step already seen.
+        let mut path = vec![*scc.iter().min().unwrap()];
+        let start = loop {
+            let last = *path.last().unwrap();
+            let next = graph
+                .neighbors_directed(last, Outgoing)
+                .filter(|n| scc.contains(n))
+                .min()
+                .unwrap();
+            if let Some(pos) = path.iter().position(|&n| n == next) {
+                break pos;
+            }
+            path.push(next);
+        };
+
+        let cycle = path[start..].iter().chain(Some(&path[start])).join(" -> ");
+        bail!("steps form a cycle: {}", cycle);
+    }
+
+    Ok(())
+}
+
+/// Orders the steps as in part1: whenever several steps are available, the
+/// lowest one goes first.
+pub fn lexical_order<N>(edges: &[(N, N, u32)]) -> Result<Vec<N>, Error>
+where
+    N: NodeTrait + fmt::Display,
+{
     let mut graph = DiGraphMap::<_, u32>::from_edges(edges);
-    let mut seq: Vec<char> = Vec::new();
+    let mut seq: Vec<N> = Vec::new();
+    check_acyclic(&graph)?;
 
     loop {
         if graph.node_count() == 0 {
             break;
         }
 
-        let options: Vec<char> = graph
+        let options: Vec<N> = graph
             .nodes()
             .filter(|n| graph.neighbors_directed(*n, Direction::Incoming).count() == 0)
             .sorted();
@@ -39,69 +104,627 @@ pub fn solve_part1(edges: &[(char, char, u32)]) -> Result<String, Error> {
         }
     }
 
-    Ok(seq.iter().collect())
+    Ok(seq)
+}
+
+#[aoc(day7, part1)]
+pub fn solve_part1(edges: &[(char, char, u32)]) -> Result<String, Error> {
+    Ok(lexical_order(edges)?.iter().collect())
+}
+
+/// The same ordering as `lexical_order`, via Kahn's algorithm: in-degrees are
+/// kept up to date as steps complete, and the steps which become ready wait in
+/// a min-heap, rather than rescanning the whole graph after every step.
+pub fn lexical_order_kahn<N>(edges: &[(N, N, u32)]) -> Result<Vec<N>, Error>
+where
+    N: Ord + Clone + fmt::Display,
+{
+    let mut successors: BTreeMap<N, Vec<N>> = BTreeMap::new();
+    let mut in_degree: BTreeMap<N, usize> = BTreeMap::new();
+
+    for (a, b, _) in edges {
+        successors.entry(a.clone()).or_default().push(b.clone());
+        in_degree.entry(a.clone()).or_insert(0);
+        *in_degree.entry(b.clone()).or_insert(0) += 1;
+    }
+
+    let mut ready: BinaryHeap<Reverse<N>> = in_degree
+        .iter()
+        .filter(|&(_, &deg)| deg == 0)
+        .map(|(n, _)| Reverse(n.clone()))
+        .collect();
+    let mut seq = Vec::with_capacity(in_degree.len());
+
+    while let Some(Reverse(step)) = ready.pop() {
+        for next in successors.get(&step).map_or(&[][..], Vec::as_slice) {
+            let deg = in_degree.get_mut(next).unwrap();
+            *deg -= 1;
+            if *deg == 0 {
+                ready.push(Reverse(next.clone()));
+            }
+        }
+        seq.push(step);
+    }
+
+    if seq.len() < in_degree.len() {
+        // a step still waiting has a predecessor that's waiting too, so walking
+        // back through them has to come around to a step already seen.
+        let waiting = |n: &N| in_degree[n] > 0;
+        let mut path = vec![in_degree.keys().find(|n| waiting(n)).unwrap().clone()];
+        let start = loop {
+            let last = path.last().unwrap();
+            let prev = edges
+                .iter()
+                .filter(|(a, b, _)| b == last && waiting(a))
+                .map(|(a, _, _)| a)
+                .min()
+                .unwrap()
+                .clone();
+            if let Some(pos) = path.iter().position(|n| *n == prev) {
+                break pos;
+            }
+            path.push(prev);
+        };
+
+        let mut cycle = path.split_off(start);
+        cycle.reverse();
+        let first = (0..cycle.len()).min_by_key(|&i| &cycle[i]).unwrap();
+        cycle.rotate_left(first);
+
+        let cycle = cycle.iter().chain(Some(&cycle[0])).join(" -> ");
+        bail!("steps form a cycle: {}", cycle);
+    }
+
+    Ok(seq)
+}
+
+#[aoc(day7, part1, heap)]
+pub fn solve_part1_heap(edges: &[(char, char, u32)]) -> Result<String, Error> {
+    Ok(lexical_order_kahn(edges)?.iter().collect())
 }
 
+#[cfg(test)]
+const SAMPLE: &str = "Step C must be finished before step A can begin.
+Step C must be finished before step F can begin.
+Step A must be finished before step B can begin.
+Step A must be finished before step D can begin.
+Step B must be finished before step E can begin.
+Step D must be finished before step E can begin.
+Step F must be finished before step E can begin.
+";
+
 #[test]
 fn test_part1_sample() {
-    let edges = input_generator("Step C must be finished before step A can begin.\nStep C must be finished before step F can begin.\nStep A must be finished before step B can begin.\nStep A must be finished before step D can begin.\nStep B must be finished before step E can begin.\nStep D must be finished before step E can begin.\nStep F must be finished before step E can begin.\n");
+    let edges = input_generator(SAMPLE).unwrap();
 
     assert_eq!(solve_part1(&edges).unwrap(), "CABDFE".to_owned());
+    assert_eq!(solve_part1_heap(&edges).unwrap(), "CABDFE".to_owned());
+
+    let labeled = [
+        ("wake", "coffee", 0),
+        ("coffee", "work", 0),
+        ("alarm", "wake", 0),
+    ];
+    assert_eq!(
+        lexical_order_kahn(&labeled).unwrap(),
+        vec!["alarm", "wake", "coffee", "work"]
+    );
+
+    let cyclic = [(1, 2, 0), (2, 3, 0), (3, 2, 0), (3, 4, 0)];
+    let err = lexical_order_kahn(&cyclic).unwrap_err();
+    assert_eq!(err.to_string(), "steps form a cycle: 2 -> 3 -> 2");
+
+    let edges = input_generator(
+        "Step C must be finished before step A can begin.
+Step A must be finished before step B can begin.
+Step B must be finished before step D can begin.
+Step D must be finished before step A can begin.
+Step D must be finished before step E can begin.
+",
+    )
+    .unwrap();
+    let err = solve_part1_heap(&edges).unwrap_err();
+    assert_eq!(err.to_string(), "steps form a cycle: A -> B -> D -> A");
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum Worker {
+enum Worker<N> {
     Idle,
-    Busy(char, u8),
+    Busy(N, u32),
 }
 
-#[aoc(day7, part2)]
-pub fn solve_part2(edges: &[(char, char, u32)]) -> Result<usize, Error> {
-    let mut graph = DiGraphMap::<_, u32>::from_edges(edges);
-    let mut workers: [Worker; 5] = [Worker::Idle; 5];
-    let mut ticks = 0;
+/// The puzzle's per-step duration on top of the base time: `A` takes 1
+/// second, `B` 2, and so on.
+pub fn letter_duration(step: char) -> u32 {
+    u32::from((step as u8) - b'A' + 1)
+}
 
-    loop {
-        if graph.node_count() == 0 {
-            if workers.iter().all(|&v| v == Worker::Idle) {
-                break;
+/// Simulates a crew of workers completing the steps of a dependency graph.
+/// Each step takes `base` seconds plus whatever `duration` returns for it;
+/// every step takes at least one second.
+pub struct Scheduler<F> {
+    workers: usize,
+    base: u32,
+    duration: F,
+}
+
+impl<F> Scheduler<F> {
+    pub fn new(workers: usize, base: u32, duration: F) -> Self {
+        Scheduler {
+            workers,
+            base,
+            duration,
+        }
+    }
+
+    /// Returns the number of seconds needed to complete every step. Available
+    /// steps are picked up in order, and idle workers take them as they
+    /// become available.
+    pub fn run<N>(&self, edges: &[(N, N, u32)]) -> Result<u32, Error>
+    where
+        N: NodeTrait + fmt::Display,
+        F: Fn(N) -> u32,
+    {
+        self.simulate(edges, |_, _, _| ())
+    }
+
+    /// Like `run`, but records what every worker was doing each second.
+    pub fn run_with_schedule<N>(&self, edges: &[(N, N, u32)]) -> Result<Schedule<N>, Error>
+    where
+        N: NodeTrait + fmt::Display,
+        F: Fn(N) -> u32,
+    {
+        let mut ticks = Vec::new();
+
+        self.simulate(edges, |second, workers, done| {
+            ticks.push(Tick {
+                second,
+                workers: workers
+                    .iter()
+                    .map(|w| match *w {
+                        Worker::Busy(step, _) => Some(step),
+                        Worker::Idle => None,
+                    })
+                    .collect(),
+                done: done.to_vec(),
+            })
+        })?;
+
+        Ok(Schedule {
+            workers: self.workers,
+            ticks,
+        })
+    }
+
+    /// Works out when each step could run given unlimited workers: its
+    /// earliest and latest start times, how much slack it has, and the
+    /// critical path through the graph.
+    pub fn analyze<N>(&self, edges: &[(N, N, u32)]) -> Result<Analysis<N>, Error>
+    where
+        N: NodeTrait + fmt::Display,
+        F: Fn(N) -> u32,
+    {
+        let graph = DiGraphMap::<N, u32>::from_edges(edges);
+        check_acyclic(&graph)?;
+
+        let order = toposort(&graph, None).map_err(|_| err_msg("steps form a cycle"))?;
+        let duration = |n: N| (self.base + (self.duration)(n)).max(1);
+
+        let mut earliest: BTreeMap<N, u32> = BTreeMap::new();
+        for &n in &order {
+            let start = graph
+                .neighbors_directed(n, Incoming)
+                .map(|p| earliest[&p] + duration(p))
+                .max()
+                .unwrap_or(0);
+            earliest.insert(n, start);
+        }
+
+        let min_time = order
+            .iter()
+            .map(|&n| earliest[&n] + duration(n))
+            .max()
+            .unwrap_or(0);
+
+        let mut latest: BTreeMap<N, u32> = BTreeMap::new();
+        for &n in order.iter().rev() {
+            let finish = graph
+                .neighbors_directed(n, Outgoing)
+                .map(|s| latest[&s])
+                .min()
+                .unwrap_or(min_time);
+            latest.insert(n, finish - duration(n));
+        }
+
+        let steps: BTreeMap<N, StepTiming> = order
+            .iter()
+            .map(|&n| {
+                let timing = StepTiming {
+                    duration: duration(n),
+                    earliest_start: earliest[&n],
+                    latest_start: latest[&n],
+                    slack: latest[&n] - earliest[&n],
+                };
+                (n, timing)
+            })
+            .collect();
+
+        // walk back from the (lexically first) step finishing last, through
+        // the predecessors it was actually waiting on.
+        let mut critical_path = Vec::new();
+        let mut cur = steps
+            .iter()
+            .find(|(_, t)| t.earliest_start + t.duration == min_time)
+            .map(|(&n, _)| n);
+
+        while let Some(n) = cur {
+            critical_path.push(n);
+            cur = graph
+                .neighbors_directed(n, Incoming)
+                .filter(|p| {
+                    steps[p].slack == 0
+                        && steps[p].earliest_start + steps[p].duration == steps[&n].earliest_start
+                })
+                .min();
+        }
+        critical_path.reverse();
+
+        Ok(Analysis {
+            steps,
+            critical_path,
+            min_time,
+        })
+    }
+
+    /// Renders the step graph in Graphviz DOT format. Each step is labeled
+    /// with its duration and its position in the part1 order, and optionally
+    /// with the worker that picked it up and when.
+    pub fn to_dot<N>(&self, edges: &[(N, N, u32)], with_schedule: bool) -> Result<String, Error>
+    where
+        N: NodeTrait + fmt::Display,
+        F: Fn(N) -> u32,
+    {
+        let graph = DiGraphMap::<N, u32>::from_edges(edges);
+        let order = lexical_order(edges)?;
+        let assignments = if with_schedule {
+            self.run_with_schedule(edges)?.assignments()
+        } else {
+            BTreeMap::new()
+        };
+
+        let mut dot = "digraph steps {\n".to_owned();
+
+        for (pos, &step) in order.iter().enumerate() {
+            let duration = (self.base + (self.duration)(step)).max(1);
+            let mut label = format!("{}\\n{}s, #{}", step, duration, pos + 1);
+            if let Some((worker, start)) = assignments.get(&step) {
+                label.push_str(&format!("\\nworker {} @ {}s", worker + 1, start));
             }
+            dot.push_str(&format!("    \"{}\" [label=\"{}\"];\n", step, label));
         }
 
-        let options: Vec<char> = graph
-            .nodes()
-            .filter(|n| graph.neighbors_directed(*n, Direction::Incoming).count() == 0)
-            .sorted();
+        for (a, b, _) in graph.all_edges().sorted_by_key(|&(a, b, _)| (a, b)) {
+            dot.push_str(&format!("    \"{}\" -> \"{}\";\n", a, b));
+        }
+
+        dot.push_str("}\n");
+        Ok(dot)
+    }
+
+    /// Runs the simulation, calling `on_tick` with the workers' assignments
+    /// and the steps completed so far at the start of every second (and once
+    /// more after the last step is done).
+    fn simulate<N>(
+        &self,
+        edges: &[(N, N, u32)],
+        mut on_tick: impl FnMut(u32, &[Worker<N>], &[N]),
+    ) -> Result<u32, Error>
+    where
+        N: NodeTrait + fmt::Display,
+        F: Fn(N) -> u32,
+    {
+        let mut graph = DiGraphMap::<N, u32>::from_edges(edges);
+        check_acyclic(&graph)?;
+
+        let mut workers: Vec<Worker<N>> = vec![Worker::Idle; self.workers];
+        let mut done = Vec::new();
+        let mut ticks = 0;
 
-        'next: for option in options {
-            for worker in &workers {
-                if let Worker::Busy(o, _) = worker {
-                    if *o == option {
-                        continue 'next;
+        loop {
+            if graph.node_count() == 0 && workers.iter().all(|&v| v == Worker::Idle) {
+                on_tick(ticks, &workers, &done);
+                break;
+            }
+
+            let options: Vec<N> = graph
+                .nodes()
+                .filter(|n| graph.neighbors_directed(*n, Direction::Incoming).count() == 0)
+                .sorted();
+
+            'next: for option in options {
+                for worker in &workers {
+                    if let Worker::Busy(o, _) = worker {
+                        if *o == option {
+                            continue 'next;
+                        }
                     }
                 }
-            }
-            match workers.iter_mut().find(|w| **w == Worker::Idle) {
-                Some(worker) => {
-                    let dur = (option as u8) - b'A' + 61;
+                if let Some(worker) = workers.iter_mut().find(|w| **w == Worker::Idle) {
+                    let dur = (self.base + (self.duration)(option)).max(1);
                     *worker = Worker::Busy(option, dur);
                 }
-                None => (),
             }
+
+            if workers.iter().all(|&v| v == Worker::Idle) {
+                bail!("no step can be started with {} workers", self.workers);
+            }
+
+            on_tick(ticks, &workers, &done);
+
+            ticks += 1;
+            workers.iter_mut().for_each(|w| {
+                if let Worker::Busy(c, d) = *w {
+                    if d == 1 {
+                        *w = Worker::Idle;
+                        graph.remove_node(c);
+                        done.push(c);
+                    } else {
+                        *w = Worker::Busy(c, d - 1);
+                    }
+                }
+            });
         }
 
-        ticks += 1;
-        workers.iter_mut().for_each(|w| {
-            if let Worker::Busy(c, d) = *w {
-                if d == 1 {
-                    *w = Worker::Idle;
-                    graph.remove_node(c);
-                } else {
-                    *w = Worker::Busy(c, d - 1);
+        Ok(ticks)
+    }
+}
+
+/// Timing of a single step when there are as many workers as needed.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct StepTiming {
+    pub duration: u32,
+    pub earliest_start: u32,
+    pub latest_start: u32,
+    /// How long the step can be delayed without delaying the whole job.
+    pub slack: u32,
+}
+
+/// The result of `Scheduler::analyze`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis<N: Ord> {
+    pub steps: BTreeMap<N, StepTiming>,
+    /// The longest chain of dependent steps; every step on it has no slack.
+    pub critical_path: Vec<N>,
+    /// The time needed to complete every step with unlimited workers.
+    pub min_time: u32,
+}
+
+/// What every worker was doing during one second of a schedule, and which
+/// steps were already complete.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Tick<N> {
+    pub second: u32,
+    pub workers: Vec<Option<N>>,
+    pub done: Vec<N>,
+}
+
+/// A second-by-second record of a `Scheduler` run. The last tick is the
+/// moment every step is finished, so its `second` is the total time taken.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Schedule<N> {
+    pub workers: usize,
+    pub ticks: Vec<Tick<N>>,
+}
+
+impl<N> Schedule<N> {
+    pub fn total_time(&self) -> u32 {
+        self.ticks.last().map_or(0, |t| t.second)
+    }
+}
+
+impl<N: Copy + Ord> Schedule<N> {
+    /// Which worker (by index) picked up each step, and at which second.
+    pub fn assignments(&self) -> BTreeMap<N, (usize, u32)> {
+        let mut assignments = BTreeMap::new();
+
+        for tick in &self.ticks {
+            for (worker, step) in tick.workers.iter().enumerate() {
+                if let Some(step) = *step {
+                    assignments.entry(step).or_insert((worker, tick.second));
                 }
             }
-        });
+        }
+
+        assignments
     }
+}
+
+impl<N: fmt::Display> Schedule<N> {
+    /// Renders the schedule as a table in the style of the puzzle's example.
+    pub fn to_table(&self) -> String {
+        let mut table = format!(
+            "Second{}   Done\n",
+            (1..=self.workers)
+                .map(|w| format!("   Worker {}", w))
+                .join("")
+        );
+
+        for tick in &self.ticks {
+            let mut row = format!("{:>6}", tick.second);
+            for worker in &tick.workers {
+                let step = worker.as_ref().map_or(".".to_owned(), |s| s.to_string());
+                row.push_str(&format!("{:>8}{:3}", step, ""));
+            }
+            row.push_str(&format!("{:>4}{}", "", tick.done.iter().join("")));
+
+            table.push_str(row.trim_end());
+            table.push('\n');
+        }
+
+        table
+    }
+
+    /// Renders the schedule as CSV, with one column per worker (empty while
+    /// idle) and the finished steps space-separated in the last column.
+    pub fn to_csv(&self) -> Result<String, Error> {
+        let mut wtr = csv::Writer::from_writer(Vec::new());
+
+        let mut header = vec!["second".to_owned()];
+        header.extend((1..=self.workers).map(|w| format!("worker_{}", w)));
+        header.push("done".to_owned());
+        wtr.write_record(&header)?;
+
+        for tick in &self.ticks {
+            let mut record = vec![tick.second.to_string()];
+            record.extend(
+                tick.workers
+                    .iter()
+                    .map(|w| w.as_ref().map_or(String::new(), |s| s.to_string())),
+            );
+            record.push(tick.done.iter().join(" "));
+            wtr.write_record(&record)?;
+        }
+
+        let data = wtr
+            .into_inner()
+            .map_err(|_| err_msg("failed to flush csv writer"))?;
+        Ok(String::from_utf8(data)?)
+    }
+}
+
+impl<N: Serialize> Schedule<N> {
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+#[aoc(day7, part2)]
+pub fn solve_part2(edges: &[(char, char, u32)]) -> Result<u32, Error> {
+    Scheduler::new(5, 60, letter_duration).run(edges)
+}
+
+#[test]
+fn test_part2_sample() {
+    let edges = input_generator(SAMPLE).unwrap();
+
+    assert_eq!(
+        Scheduler::new(2, 0, letter_duration).run(&edges).unwrap(),
+        15
+    );
+    assert_eq!(
+        Scheduler::new(1, 0, letter_duration).run(&edges).unwrap(),
+        21
+    );
+    assert_eq!(Scheduler::new(2, 0, |_| 1).run(&edges).unwrap(), 4);
+    assert!(Scheduler::new(0, 0, letter_duration).run(&edges).is_err());
+
+    // step names don't have to be letters.
+    let numbered = [(1, 2, 0), (1, 3, 0), (3, 4, 0)];
+    assert_eq!(
+        Scheduler::new(2, 10, |n: u32| n).run(&numbered).unwrap(),
+        38
+    );
+}
+
+#[test]
+fn test_part2_schedule() {
+    let edges = input_generator(SAMPLE).unwrap();
+    let schedule = Scheduler::new(2, 0, letter_duration)
+        .run_with_schedule(&edges)
+        .unwrap();
+
+    assert_eq!(schedule.total_time(), 15);
+    assert_eq!(
+        schedule.to_table(),
+        "Second   Worker 1   Worker 2   Done
+     0       C          .
+     1       C          .
+     2       C          .
+     3       A          F       C
+     4       B          F       CA
+     5       B          F       CA
+     6       D          F       CAB
+     7       D          F       CAB
+     8       D          F       CAB
+     9       D          .       CABF
+    10       E          .       CABFD
+    11       E          .       CABFD
+    12       E          .       CABFD
+    13       E          .       CABFD
+    14       E          .       CABFD
+    15       .          .       CABFDE
+"
+    );
+
+    let csv = schedule.to_csv().unwrap();
+    assert_eq!(csv.lines().next(), Some("second,worker_1,worker_2,done"));
+    assert_eq!(csv.lines().nth(5), Some("4,B,F,C A"));
+
+    let json = schedule.to_json().unwrap();
+    assert!(json.contains(r#"{"second":4,"workers":["B","F"],"done":["C","A"]}"#));
+}
+
+#[test]
+fn test_invalid_input() {
+    let err = input_generator("Step C must be finished before step A can begin.\nStep A must be finished before step A can begin.\n").unwrap_err();
+    assert_eq!(err.to_string(), "line 2: step A can't depend on itself");
+
+    let err = input_generator("Step C must be finished before step A can begin.\n\nStep C must finish before step F can begin.\n").unwrap_err();
+    assert!(err.to_string().starts_with("line 3: expected"));
+
+    assert!(input_generator("Step c must be finished before step A can begin.").is_err());
+    assert!(input_generator("Step CD must be finished before step A can begin.").is_err());
+
+    let edges = input_generator("Step C must be finished before step A can begin.\nStep A must be finished before step B can begin.\nStep B must be finished before step D can begin.\nStep D must be finished before step A can begin.\nStep D must be finished before step E can begin.\n").unwrap();
+    let expected = "steps form a cycle: A -> B -> D -> A";
+    assert_eq!(solve_part1(&edges).unwrap_err().to_string(), expected);
+    let err = Scheduler::new(2, 0, letter_duration)
+        .run(&edges)
+        .unwrap_err();
+    assert_eq!(err.to_string(), expected);
+}
+
+#[test]
+fn test_analyze() {
+    let edges = input_generator(SAMPLE).unwrap();
+    let scheduler = Scheduler::new(2, 0, letter_duration);
+    let analysis = scheduler.analyze(&edges).unwrap();
+
+    assert_eq!(analysis.min_time, 14);
+    assert_eq!(analysis.critical_path, vec!['C', 'F', 'E']);
+    assert_eq!(
+        analysis.steps[&'D'],
+        StepTiming {
+            duration: 4,
+            earliest_start: 4,
+            latest_start: 5,
+            slack: 1,
+        }
+    );
+
+    let slack: Vec<u32> = analysis.steps.values().map(|t| t.slack).collect();
+    assert_eq!(slack, vec![1, 3, 0, 1, 0, 0]);
+
+    // two workers can't keep up with the critical path here.
+    assert_eq!(scheduler.run(&edges).unwrap(), 15);
+    assert_eq!(
+        Scheduler::new(3, 0, letter_duration).run(&edges).unwrap(),
+        14
+    );
+}
+
+#[test]
+fn test_to_dot() {
+    let edges = input_generator(SAMPLE).unwrap();
+    let scheduler = Scheduler::new(2, 0, letter_duration);
+
+    let dot = scheduler.to_dot(&edges, false).unwrap();
+    assert!(dot.starts_with("digraph steps {\n    \"C\" [label=\"C\\n3s, #1\"];\n"));
+    assert!(dot.contains("    \"F\" [label=\"F\\n6s, #5\"];\n"));
+    assert!(dot.contains("    \"A\" -> \"B\";\n"));
+    assert_eq!(dot.matches(" -> ").count(), 7);
 
-    Ok(ticks)
+    let dot = scheduler.to_dot(&edges, true).unwrap();
+    assert!(dot.contains("    \"F\" [label=\"F\\n6s, #5\\nworker 2 @ 3s\"];\n"));
+    assert!(dot.contains("    \"E\" [label=\"E\\n5s, #6\\nworker 1 @ 10s\"];\n"));
 }
@@ -1,132 +1,343 @@
-use arrayvec::ArrayVec;
-use failure::{bail, err_msg, Error};
-use petgraph::prelude::*;
+use failure::{bail, err_msg, format_err, Error};
+use itertools::Itertools;
+use serde::Serialize;
+use std::fmt;
 use std::str::FromStr;
 
 struct NodeWeight {
     ch_num: usize,
     md_num: usize,
-    md_sum: u32,
-    md: ArrayVec<[u8; 16]>,
+    md_sum: u64,
+    md: Vec<u32>,
+    children: Vec<usize>,
 }
 
 impl NodeWeight {
-    pub fn new(ch_num: u8, md_num: u8) -> Self {
+    pub fn new(ch_num: u32, md_num: u32) -> Self {
         NodeWeight {
             ch_num: ch_num as usize,
             md_num: md_num as usize,
             md_sum: 0,
-            md: ArrayVec::new(),
+            md: Vec::new(),
+            children: Vec::new(),
         }
     }
 
-    pub fn ingest_md(&mut self, data: &mut Iterator<Item = u8>) -> Result<(), Error> {
+    pub fn ingest_md(&mut self, data: &mut dyn Iterator<Item = u32>) -> Result<(), Error> {
         if self.md_num > 0 {
-            if self.md.len() > 0 {
+            if !self.md.is_empty() {
                 bail!("metadata alredy assigned to node");
             };
             self.md.extend(data.take(self.md_num));
-            if self.md.len() < self.md_num {
-                bail!("insufficient metadata values for node");
-            }
-            self.md_sum = self.md.iter().map(|&v| v as u32).sum();
+            self.md_sum = self.md.iter().map(|&v| u64::from(v)).sum();
         }
         Ok(())
     }
 }
 
+/// A node as exported by `Tree::to_json`. Ids are node indices, which
+/// number the nodes in depth-first order from the root at 0.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NodeRecord {
+    pub id: usize,
+    pub depth: usize,
+    pub children: Vec<usize>,
+    pub metadata: Vec<u32>,
+    pub md_sum: u64,
+    pub subtree_md_sum: u64,
+    pub value: u64,
+}
+
+/// Depth-first, pre-order walk over a `Tree`, yielding each node with its
+/// depth below the root.
+pub struct DepthFirst<'a> {
+    tree: &'a Tree,
+    stack: Vec<(usize, usize)>,
+}
+
+impl<'a> Iterator for DepthFirst<'a> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (idx, depth) = self.stack.pop()?;
+        let children = &self.tree.nodes[idx].children;
+        self.stack
+            .extend(children.iter().rev().map(|&cidx| (cidx, depth + 1)));
+        Some((idx, depth))
+    }
+}
+
+/// Nodes live in one arena, in the order their headers appear in the input,
+/// and refer to their children by index into it.
 pub struct Tree {
-    graph: Graph<NodeWeight, ()>,
-    stack: Vec<NodeIndex>,
-    root: Option<NodeIndex>,
+    nodes: Vec<NodeWeight>,
+    stack: Vec<usize>,
+    root: Option<usize>,
+}
+
+impl Default for Tree {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Tree {
     pub fn new() -> Self {
         Tree {
-            graph: Graph::new(),
+            nodes: Vec::new(),
             stack: Vec::new(),
             root: None,
         }
     }
 
-    pub fn md_sum(&self) -> u32 {
-        self.graph
-            .node_indices()
-            .map(|idx| {
-                let weight = self.graph.node_weight(idx).unwrap();
-                weight.md_sum
-            })
-            .sum()
-    }
-
-    pub fn root_value(&self) -> Result<u32, Error> {
-        let root_idx = self.root.ok_or(err_msg("tree has no root node"))?;
-        Ok(self.node_value(root_idx))
-    }
-
-    fn node_value(&self, idx: NodeIndex) -> u32 {
-        let weight = self.graph.node_weight(idx).unwrap();
-
-        match weight.ch_num {
-            0 => weight.md_sum,
-            _ => weight.md.iter().map(|&mdval| {
-                if mdval == 0 {
-                    0
-                } else {
-                    let mut children: ArrayVec<[NodeIndex; 16]> =
-                        self.graph.neighbors_directed(idx, Outgoing).collect();
-                    children.reverse();
-                    if let Some(cnode_idx) = children.get(mdval as usize - 1) {
-                        self.node_value(*cnode_idx)
-                    } else {
-                        0
-                    }
+    pub fn md_sum(&self) -> u64 {
+        self.nodes.iter().map(|weight| weight.md_sum).sum()
+    }
+
+    pub fn root_value(&self) -> Result<u64, Error> {
+        let root_idx = self.root.ok_or_else(|| err_msg("tree has no root node"))?;
+        Ok(self.node_values(root_idx)?[root_idx])
+    }
+
+    pub fn root(&self) -> Option<usize> {
+        self.root
+    }
+
+    pub fn depth_first(&self) -> DepthFirst<'_> {
+        DepthFirst {
+            tree: self,
+            stack: self.root.iter().map(|&root| (root, 0)).collect(),
+        }
+    }
+
+    /// The children of `idx`, in the order they appear in the input.
+    pub fn children(&self, idx: usize) -> &[usize] {
+        &self.nodes[idx].children
+    }
+
+    pub fn metadata(&self, idx: usize) -> &[u32] {
+        &self.nodes[idx].md
+    }
+
+    /// The deepest node and its depth; the first one met depth-first on ties.
+    pub fn deepest(&self) -> Option<(usize, usize)> {
+        self.depth_first().fold(
+            None,
+            |deepest: Option<(usize, usize)>, (idx, depth)| match deepest {
+                Some((_, max)) if max >= depth => deepest,
+                _ => Some((idx, depth)),
+            },
+        )
+    }
+
+    /// Metadata sum of every node's subtree, indexed by node index.
+    pub fn subtree_md_sums(&self) -> Vec<u64> {
+        let mut sums = vec![0; self.nodes.len()];
+        // nodes are added parent first, so walking the indices backwards
+        // reaches every child before its parent
+        for (idx, weight) in self.nodes.iter().enumerate().rev() {
+            sums[idx] = weight.md_sum + weight.children.iter().map(|&c| sums[c]).sum::<u64>();
+        }
+        sums
+    }
+
+    /// Value of every node, indexed by node index.
+    pub fn values(&self) -> Result<Vec<u64>, Error> {
+        match self.root {
+            Some(root) => self.node_values(root),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        let (sums, values) = (self.subtree_md_sums(), self.values()?);
+        let nodes: Vec<NodeRecord> = self
+            .depth_first()
+            .map(|(idx, depth)| {
+                let weight = &self.nodes[idx];
+                NodeRecord {
+                    id: idx,
+                    depth,
+                    children: weight.children.clone(),
+                    metadata: weight.md.clone(),
+                    md_sum: weight.md_sum,
+                    subtree_md_sum: sums[idx],
+                    value: values[idx],
                 }
-            }).sum(),
+            })
+            .collect();
+        Ok(serde_json::to_string(&nodes)?)
+    }
+
+    /// Values of every node under `idx`, indexed by node index. Nodes are
+    /// evaluated in post-order off an explicit stack, so each value is computed
+    /// once, after its children's, no matter how deep the tree goes. A node
+    /// can count the same child many times over, so values can double with
+    /// every level, and outgrowing a `u64` is an error.
+    fn node_values(&self, idx: usize) -> Result<Vec<u64>, Error> {
+        let mut values = vec![0; self.nodes.len()];
+        let mut stack = vec![(idx, false)];
+
+        while let Some((idx, visited)) = stack.pop() {
+            let weight = &self.nodes[idx];
+            if !visited {
+                stack.push((idx, true));
+                stack.extend(weight.children.iter().map(|&cidx| (cidx, false)));
+                continue;
+            }
+
+            values[idx] = match weight.ch_num {
+                0 => weight.md_sum,
+                _ => weight
+                    .md
+                    .iter()
+                    .filter_map(|&mdval| weight.children.get((mdval as usize).checked_sub(1)?))
+                    .try_fold(0u64, |sum, &cidx| sum.checked_add(values[cidx]))
+                    .ok_or_else(|| err_msg("node value overflows a u64"))?,
+            };
+        }
+
+        Ok(values)
+    }
+
+    /// The tree flattened back into the "children metadata ..." number
+    /// stream it was parsed from.
+    pub fn to_numbers(&self) -> Vec<u32> {
+        let mut numbers = Vec::new();
+        let mut stack: Vec<(usize, bool)> = self.root.iter().map(|&r| (r, false)).collect();
+
+        while let Some((idx, visited)) = stack.pop() {
+            let weight = &self.nodes[idx];
+            if visited {
+                numbers.extend(&weight.md);
+                continue;
+            }
+            numbers.push(weight.ch_num as u32);
+            numbers.push(weight.md_num as u32);
+            stack.push((idx, true));
+            stack.extend(weight.children.iter().rev().map(|&cidx| (cidx, false)));
         }
+
+        numbers
     }
 
-    pub fn ingest_data(&mut self, data: &mut impl Iterator<Item = u8>) -> Result<(), Error> {
+    /// One line per node, indented by depth, showing its header, metadata,
+    /// metadata sum and value:
+    ///
+    /// ```text
+    /// [2 3] 1 1 2 (sum 4, value 66)
+    ///   [0 3] 10 11 12 (sum 33, value 33)
+    /// ```
+    pub fn pretty(&self) -> Result<String, Error> {
+        let values = self.values()?;
+        let mut out = String::new();
+
+        for (idx, depth) in self.depth_first() {
+            let weight = &self.nodes[idx];
+            out += &format!(
+                "{:indent$}[{} {}] {} (sum {}, value {})\n",
+                "",
+                weight.ch_num,
+                weight.md_num,
+                weight.md.iter().join(" "),
+                weight.md_sum,
+                values[idx],
+                indent = depth * 2
+            );
+        }
+
+        Ok(out)
+    }
+
+    pub fn ingest_data(&mut self, data: &mut impl Iterator<Item = u32>) -> Result<(), Error> {
         let mut data = data.peekable();
         while data.peek().is_some() {
             match self.stack.last() {
+                None if self.root.is_some() => {
+                    bail!("{} trailing values after the root node", data.count());
+                }
                 None => {
                     self.ingest_node(&mut data)?;
                 }
                 Some(idx) => {
-                    let child_count = self.graph.edges_directed(*idx, Outgoing).count();
-                    let weight = self.graph.node_weight_mut(*idx).unwrap();
+                    let weight = &mut self.nodes[*idx];
 
-                    if child_count < weight.ch_num {
+                    if weight.children.len() < weight.ch_num {
                         self.ingest_node(&mut data)?;
                         continue;
                     }
 
                     weight.ingest_md(&mut data)?;
-                    self.stack.pop();
+                    if weight.md.len() == weight.md_num {
+                        self.stack.pop();
+                    }
                 }
             }
         }
 
+        // nodes without metadata close without consuming anything further
+        while let Some(idx) = self.stack.last() {
+            let weight = &self.nodes[*idx];
+            if weight.children.len() < weight.ch_num || weight.md.len() < weight.md_num {
+                return Err(self.unclosed_error("input ended"));
+            }
+            self.stack.pop();
+        }
+
         Ok(())
     }
 
-    fn ingest_node(&mut self, data: &mut Iterator<Item = u8>) -> Result<(), Error> {
-        let ch_num = data.next().ok_or(err_msg("expected missing child count"))?;
-        let md_num = data
+    /// Describes the nodes left open on the stack, innermost last.
+    fn unclosed_error(&self, context: &str) -> Error {
+        const SHOWN: usize = 8;
+
+        let skipped = self.stack.len().saturating_sub(SHOWN);
+        let nodes = self
+            .stack
+            .iter()
+            .enumerate()
+            .skip(skipped)
+            .map(|(depth, &idx)| {
+                let weight = &self.nodes[idx];
+                format!(
+                    "depth {} [{} {}] has {}/{} children, {}/{} metadata",
+                    depth,
+                    weight.ch_num,
+                    weight.md_num,
+                    weight.children.len(),
+                    weight.ch_num,
+                    weight.md.len(),
+                    weight.md_num
+                )
+            })
+            .join("; ");
+
+        format_err!(
+            "{} with {} unclosed nodes: {}{}",
+            context,
+            self.stack.len(),
+            if skipped > 0 { "...; " } else { "" },
+            nodes
+        )
+    }
+
+    fn ingest_node(&mut self, data: &mut dyn Iterator<Item = u32>) -> Result<(), Error> {
+        let ch_num = data
             .next()
-            .ok_or(err_msg("missing metadata record count"))?;
+            .ok_or_else(|| err_msg("expected missing child count"))?;
+        let md_num = match data.next() {
+            Some(md_num) => md_num,
+            None => return Err(self.unclosed_error("input ended inside a node header")),
+        };
 
-        self.stack
-            .push(self.graph.add_node(NodeWeight::new(ch_num, md_num)));
+        let idx = self.nodes.len();
+        self.nodes.push(NodeWeight::new(ch_num, md_num));
 
-        if self.stack.len() > 1 {
-            let pidx = self.stack.get(self.stack.len() - 2).unwrap();
-            self.graph.add_edge(*pidx, *self.stack.last().unwrap(), ());
-        } else {
-            self.root = self.stack.last().cloned();
+        match self.stack.last() {
+            Some(&pidx) => self.nodes[pidx].children.push(idx),
+            None => self.root = Some(idx),
         }
+        self.stack.push(idx);
 
         Ok(())
     }
@@ -136,19 +347,32 @@ impl FromStr for Tree {
     type Err = Error;
     fn from_str(s: &str) -> Result<Tree, Self::Err> {
         let mut t = Tree::new();
-        let mut data_iter = s.split(' ').map(|v| v.parse::<u8>().unwrap());
-        t.ingest_data(&mut data_iter)?;
+        let data = s
+            .split_whitespace()
+            .enumerate()
+            .map(|(pos, v)| {
+                v.parse::<u32>()
+                    .map_err(|e| format_err!("invalid value {:?} at position {}: {}", v, pos, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        t.ingest_data(&mut data.into_iter())?;
         Ok(t)
     }
 }
 
+impl fmt::Display for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_numbers().iter().join(" "))
+    }
+}
+
 #[aoc_generator(day8)]
 pub fn input_generator(input: &str) -> Result<Box<Tree>, Error> {
     Ok(Box::new(input.parse()?))
 }
 
 #[aoc(day8, part1)]
-pub fn solve_part1(tree: &Tree) -> Result<u32, Error> {
+pub fn solve_part1(tree: &Tree) -> Result<u64, Error> {
     Ok(tree.md_sum())
 }
 
@@ -159,8 +383,8 @@ fn test_part1_sample() {
 }
 
 #[aoc(day8, part2)]
-pub fn solve_part2(tree: &Tree) -> Result<u32, Error> {
-    Ok(tree.root_value()?)
+pub fn solve_part2(tree: &Tree) -> Result<u64, Error> {
+    tree.root_value()
 }
 
 #[test]
@@ -168,3 +392,128 @@ fn test_part2_sample() {
     let tree = input_generator("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
     assert_eq!(solve_part2(&tree).unwrap(), 66);
 }
+
+#[test]
+fn test_wide_nodes() {
+    // a root with 20 leaf children, each carrying 20 metadata entries above 255
+    let leaf = format!("0 20{}", " 1000".repeat(20));
+    let refs = (1..=21)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let input = format!("20 21{} {}", format!(" {}", leaf).repeat(20), refs);
+    let tree = input_generator(&input).unwrap();
+    assert_eq!(solve_part1(&tree).unwrap(), 20 * 20 * 1000 + 231);
+    assert_eq!(solve_part2(&tree).unwrap(), 20 * 20 * 1000);
+}
+
+#[test]
+fn test_invalid_values() {
+    assert!(input_generator("1 1 0 1 x 1").is_err());
+    assert!(input_generator("0 1 4294967296").is_err());
+    assert!(input_generator("1 1 0 2 3").is_err());
+
+    // headers can't claim more room than the input has values for
+    assert!(input_generator("4294967295 4294967295").is_err());
+    assert!(input_generator("0 4294967295 1 2 3").is_err());
+
+    // metadata sums outgrow the values themselves
+    let tree = input_generator("0 2 4294967295 1").unwrap();
+    assert_eq!(solve_part1(&tree).unwrap(), 4294967296);
+    assert_eq!(solve_part2(&tree).unwrap(), 4294967296);
+}
+
+#[test]
+fn test_deep_tree() {
+    let depth = 200_000;
+    let input = format!("{}0 1 5{}", "1 1 ".repeat(depth), " 1".repeat(depth));
+    let tree = input_generator(&input).unwrap();
+    assert_eq!(solve_part1(&tree).unwrap(), 5 + depth as u64);
+    assert_eq!(solve_part2(&tree).unwrap(), 5);
+
+    // every node counts its only child twice, doubling the value per level
+    let chain = |depth| format!("{}0 1 5{}", "1 2 ".repeat(depth), " 1 1".repeat(depth));
+    let tree = input_generator(&chain(40)).unwrap();
+    assert_eq!(solve_part2(&tree).unwrap(), 5 << 40);
+    let tree = input_generator(&chain(70)).unwrap();
+    assert!(solve_part2(&tree).is_err());
+    assert!(tree.to_json().is_err());
+}
+
+#[test]
+fn test_serialize() {
+    let input = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
+    let tree = input_generator(input).unwrap();
+    assert_eq!(tree.to_string(), input);
+    assert_eq!(
+        input_generator(&tree.to_string()).unwrap().to_string(),
+        input
+    );
+
+    assert_eq!(
+        tree.pretty().unwrap(),
+        "[2 3] 1 1 2 (sum 4, value 66)\n\
+         \x20 [0 3] 10 11 12 (sum 33, value 33)\n\
+         \x20 [1 1] 2 (sum 2, value 0)\n\
+         \x20   [0 1] 99 (sum 99, value 99)\n"
+    );
+}
+
+#[test]
+fn test_malformed_streams() {
+    let err = input_generator("0 1 5 0 1 6").err().unwrap();
+    assert_eq!(err.to_string(), "3 trailing values after the root node");
+
+    let err = input_generator("2 3 0 3 10 11 12 1 1 0 1 99 2 1")
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "input ended with 1 unclosed nodes: depth 0 [2 3] has 2/2 children, 1/3 metadata"
+    );
+
+    let err = input_generator("2 1 0 0 1 1 0").err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "input ended inside a node header with 2 unclosed nodes: \
+         depth 0 [2 1] has 2/2 children, 0/1 metadata; \
+         depth 1 [1 1] has 0/1 children, 0/1 metadata"
+    );
+
+    let tree = input_generator("2 3 0 3 10 11 12 1 1 0 1\n99 2  1 1 2\n").unwrap();
+    assert_eq!(solve_part2(&tree).unwrap(), 66);
+    assert_eq!(input_generator("1 0 0 0").unwrap().to_string(), "1 0 0 0");
+}
+
+#[test]
+fn test_queries() {
+    let tree = input_generator("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+    let root = tree.root().unwrap();
+
+    let walk: Vec<_> = tree
+        .depth_first()
+        .map(|(idx, depth)| (tree.metadata(idx).to_vec(), depth))
+        .collect();
+    assert_eq!(
+        walk,
+        vec![
+            (vec![1, 1, 2], 0),
+            (vec![10, 11, 12], 1),
+            (vec![2], 1),
+            (vec![99], 2)
+        ]
+    );
+
+    let children = tree.children(root);
+    assert_eq!(tree.metadata(children[1]), &[2]);
+    let (deepest, depth) = tree.deepest().unwrap();
+    assert_eq!((tree.metadata(deepest), depth), (&[99][..], 2));
+
+    assert_eq!(tree.subtree_md_sums(), vec![138, 33, 101, 99]);
+    assert_eq!(tree.values().unwrap(), vec![66, 33, 0, 99]);
+
+    let json = tree.to_json().unwrap();
+    assert!(json.starts_with(
+        r#"[{"id":0,"depth":0,"children":[1,2],"metadata":[1,1,2],"md_sum":4,"subtree_md_sum":138,"value":66}"#
+    ));
+}
@@ -1,9 +1,7 @@
-use arrayvec::ArrayVec;
-use failure::{bail, err_msg, Error};
+use failure::{bail, err_msg, format_err, Error};
 use hashbrown::HashMap;
-use petgraph::prelude::*;
+use std::cmp::Reverse;
 use std::collections::VecDeque;
-use std::str::FromStr;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Params {
@@ -11,22 +9,81 @@ pub struct Params {
     last_marble: u64,
 }
 
+impl Params {
+    pub fn new(players: u32, last_marble: u64) -> Params {
+        Params {
+            players,
+            last_marble,
+        }
+    }
+
+    /// The same game, played out to a last marble `factor` times larger.
+    pub fn scaled(&self, factor: u64) -> Result<Params, Error> {
+        Ok(Params {
+            players: self.players,
+            last_marble: self.last_marble.checked_mul(factor).ok_or_else(|| {
+                format_err!(
+                    "last marble {} times {} overflows",
+                    self.last_marble,
+                    factor
+                )
+            })?,
+        })
+    }
+}
+
+/// How much larger part 2's last marble is than the input's.
+pub const PART2_MULTIPLIER: u64 = 100;
+
+/// The parameters of a marble game: marbles numbered by a multiple of
+/// `special_multiple` are kept, scoring along with the marble `remove_offset`
+/// places from the current one; every other marble is placed after moving
+/// `insert_offset` places. Negative offsets count counter-clockwise.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rules {
+    pub special_multiple: u64,
+    pub remove_offset: isize,
+    pub insert_offset: isize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            special_multiple: 23,
+            remove_offset: -7,
+            insert_offset: 2,
+        }
+    }
+}
+
 #[aoc_generator(day9)]
 pub fn input_generator(input: &str) -> Result<Box<Params>, Error> {
     let mut it = input.split(' ');
     Ok(Box::new(Params {
         players: it
             .next()
-            .ok_or(err_msg("missing number of players"))?
+            .ok_or_else(|| err_msg("missing number of players"))?
             .parse()?,
         last_marble: it
-            .skip(5)
-            .next()
-            .ok_or(err_msg("missing last marble worth"))?
+            .nth(5)
+            .ok_or_else(|| err_msg("missing last marble worth"))?
             .parse()?,
     }))
 }
 
+/// The marble circle, as seen from the current marble: `rotate` moves the
+/// current marble clockwise (or counter-clockwise, for negative offsets),
+/// `add_marble` places a marble just counter-clockwise of the current one and
+/// makes it current, and `remove_marble` takes the current marble out, leaving
+/// its clockwise neighbour current.
+trait MarbleBoard<T>: Default {
+    fn add_marble(&mut self, value: T);
+    fn remove_marble(&mut self) -> Option<T>;
+    fn rotate(&mut self, offset: isize);
+    /// Every marble, clockwise from the current one.
+    fn marbles(&self) -> Vec<T>;
+}
+
 #[derive(Clone, Debug)]
 struct Board<T> {
     storage: VecDeque<T>,
@@ -39,15 +96,35 @@ impl<T> Board<T> {
         }
     }
 
-    pub fn add_marble(&mut self, value: T) {
+    fn next(&mut self) {
+        if let Some(v) = self.storage.pop_front() {
+            self.storage.push_back(v);
+        }
+    }
+
+    fn prev(&mut self) {
+        if let Some(v) = self.storage.pop_back() {
+            self.storage.push_front(v);
+        }
+    }
+}
+
+impl<T> Default for Board<T> {
+    fn default() -> Self {
+        Board::new()
+    }
+}
+
+impl<T: Copy> MarbleBoard<T> for Board<T> {
+    fn add_marble(&mut self, value: T) {
         self.storage.push_front(value);
     }
 
-    pub fn remove_marble(&mut self) -> Option<T> {
+    fn remove_marble(&mut self) -> Option<T> {
         self.storage.pop_front()
     }
 
-    pub fn rotate(&mut self, offset: isize) {
+    fn rotate(&mut self, offset: isize) {
         if offset == 0 || self.storage.len() <= 1 {
             return;
         }
@@ -63,39 +140,189 @@ impl<T> Board<T> {
         }
     }
 
-    fn next(&mut self) {
-        if let Some(v) = self.storage.pop_front() {
-            self.storage.push_back(v);
+    fn marbles(&self) -> Vec<T> {
+        self.storage.iter().cloned().collect()
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Link {
+    prev: usize,
+    next: usize,
+}
+
+/// A marble circle kept as a ring of slots linked by index: placing or
+/// removing a marble relinks its two neighbours, and rotating walks the links
+/// rather than moving marbles between the ends of a deque. Slots freed by
+/// removed marbles are reused.
+#[derive(Clone, Debug)]
+struct RingBoard<T> {
+    values: Vec<T>,
+    links: Vec<Link>,
+    free: Vec<usize>,
+    current: Option<usize>,
+}
+
+impl<T> RingBoard<T> {
+    pub fn new() -> RingBoard<T> {
+        RingBoard {
+            values: Vec::new(),
+            links: Vec::new(),
+            free: Vec::new(),
+            current: None,
         }
     }
 
-    fn prev(&mut self) {
-        if let Some(v) = self.storage.pop_back() {
-            self.storage.push_front(v);
+    fn alloc(&mut self, value: T) -> usize {
+        match self.free.pop() {
+            Some(slot) => {
+                self.values[slot] = value;
+                slot
+            }
+            None => {
+                self.values.push(value);
+                let slot = self.values.len() - 1;
+                self.links.push(Link {
+                    prev: slot,
+                    next: slot,
+                });
+                self.values.len() - 1
+            }
         }
     }
 }
 
+impl<T> Default for RingBoard<T> {
+    fn default() -> Self {
+        RingBoard::new()
+    }
+}
+
+impl<T: Copy> MarbleBoard<T> for RingBoard<T> {
+    fn add_marble(&mut self, value: T) {
+        let slot = self.alloc(value);
+
+        if let Some(cur) = self.current {
+            let before = self.links[cur].prev;
+            self.links[slot].prev = before;
+            self.links[slot].next = cur;
+            self.links[before].next = slot;
+            self.links[cur].prev = slot;
+        } else {
+            self.links[slot].prev = slot;
+            self.links[slot].next = slot;
+        }
+
+        self.current = Some(slot);
+    }
+
+    fn remove_marble(&mut self) -> Option<T> {
+        let cur = self.current?;
+        let (before, after) = (self.links[cur].prev, self.links[cur].next);
+
+        if after == cur {
+            self.current = None;
+        } else {
+            self.links[before].next = after;
+            self.links[after].prev = before;
+            self.current = Some(after);
+        }
+
+        self.free.push(cur);
+        Some(self.values[cur])
+    }
+
+    fn rotate(&mut self, offset: isize) {
+        if let Some(mut cur) = self.current {
+            if offset < 0 {
+                for _ in 0..(-offset) {
+                    cur = self.links[cur].prev;
+                }
+            } else {
+                for _ in 0..offset {
+                    cur = self.links[cur].next;
+                }
+            }
+            self.current = Some(cur);
+        }
+    }
+
+    fn marbles(&self) -> Vec<T> {
+        let mut marbles = Vec::new();
+        if let Some(start) = self.current {
+            let mut cur = start;
+            loop {
+                marbles.push(self.values[cur]);
+                cur = self.links[cur].next;
+                if cur == start {
+                    break;
+                }
+            }
+        }
+        marbles
+    }
+}
+
+/// A special marble kept by `player`. Marble `turn` is the one placed on that
+/// turn, so the player scores `turn + removed`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScoringEvent {
+    pub turn: u64,
+    pub player: u32,
+    pub removed: u64,
+}
+
+/// Final scores of every player, highest first (ties by player id), and
+/// every scoring event in the order they happened. Players are numbered from
+/// 1, as in the puzzle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scoreboard {
+    pub scores: Vec<(u32, u64)>,
+    pub events: Vec<ScoringEvent>,
+}
+
+impl Scoreboard {
+    /// The winning player, or `None` if nobody scored.
+    pub fn winner(&self) -> Option<u32> {
+        self.scores
+            .first()
+            .filter(|&&(_, score)| score > 0)
+            .map(|&(player, _)| player)
+    }
+
+    /// The winning player's score, or `None` if nobody scored.
+    pub fn winning_score(&self) -> Option<u64> {
+        self.scores
+            .first()
+            .filter(|&&(_, score)| score > 0)
+            .map(|&(_, score)| score)
+    }
+}
+
 #[derive(Debug)]
-struct Game {
-    board: Board<u64>,
+struct Game<B = Board<u64>> {
+    board: B,
     next_marble: u64,
     cur_player: u32,
     params: Params,
+    rules: Rules,
     scores: HashMap<u32, u64>,
+    events: Vec<ScoringEvent>,
 }
 
-impl Game {
-    pub fn new(params: Params) -> Game {
-        let mut board = Board::new();
+impl<B: MarbleBoard<u64>> Game<B> {
+    pub fn new(params: Params, rules: Rules) -> Game<B> {
+        let mut board = B::default();
         board.add_marble(0);
 
         Game {
             board,
             params,
+            rules,
             next_marble: 1,
             cur_player: 0,
             scores: HashMap::new(),
+            events: Vec::new(),
         }
     }
 
@@ -108,13 +335,18 @@ impl Game {
             return None;
         }
 
-        if (self.next_marble % 23) == 0 {
-            self.board.rotate(-7);
+        if self.next_marble.is_multiple_of(self.rules.special_multiple) {
+            self.board.rotate(self.rules.remove_offset);
+            let removed = self.board.remove_marble().unwrap();
 
-            *self.scores.entry(self.cur_player).or_insert(0) +=
-                self.next_marble + self.board.remove_marble().unwrap();
+            *self.scores.entry(self.cur_player).or_insert(0) += self.next_marble + removed;
+            self.events.push(ScoringEvent {
+                turn: self.next_marble,
+                player: self.cur_player + 1,
+                removed,
+            });
         } else {
-            self.board.rotate(2);
+            self.board.rotate(self.rules.insert_offset);
             self.board.add_marble(self.next_marble);
         }
 
@@ -129,50 +361,274 @@ impl Game {
             .scores
             .values()
             .max()
-            .ok_or(err_msg("no player scored any points"))?)
+            .ok_or_else(|| err_msg("no player scored any points"))?)
+    }
+
+    pub fn scoreboard(&self) -> Scoreboard {
+        let mut scores: Vec<(u32, u64)> = (0..self.params.players)
+            .map(|p| (p + 1, self.scores.get(&p).cloned().unwrap_or(0)))
+            .collect();
+        scores.sort_by_key(|&(player, score)| (Reverse(score), player));
+
+        Scoreboard {
+            scores,
+            events: self.events.clone(),
+        }
+    }
+
+    /// The circle in the puzzle's format, starting from marble 0 (or the
+    /// lowest marble left), with the current marble in parentheses and each
+    /// marble padded to `width`.
+    pub fn render_board(&self, width: usize) -> String {
+        let mut marbles = self.board.marbles();
+        let start = (0..marbles.len()).min_by_key(|&i| marbles[i]).unwrap_or(0);
+        let current = (marbles.len() - start) % marbles.len().max(1);
+        marbles.rotate_left(start);
+
+        let mut out = String::new();
+        for (i, marble) in marbles.iter().enumerate() {
+            let cell = if i == current {
+                format!("{:>width$}", format!("({}", marble), width = width)
+            } else {
+                format!("{:>width$}", marble, width = width)
+            };
+            // the closing parenthesis takes the place of the next cell's padding
+            if i > 0 && i == current + 1 {
+                out.push(')');
+                out += &cell[1..];
+            } else {
+                out += &cell;
+            }
+        }
+        if current + 1 == marbles.len() {
+            out.push(')');
+        }
+
+        out
     }
 }
 
-#[aoc(day9, part1)]
-pub fn solve_part1(params: &Params) -> Result<u64, Error> {
-    let mut game = Game::new(*params);
+fn check_game(params: &Params, rules: &Rules) -> Result<(), Error> {
+    if params.players == 0 {
+        bail!("the game needs at least one player");
+    }
+    // no marble numbered from 1 up is a multiple of 0, so nobody could score
+    if rules.special_multiple == 0 {
+        bail!("the special marble multiple must not be 0");
+    }
+    // with every marble special, the circle empties and there's nothing left to remove
+    if rules.special_multiple == 1 {
+        bail!("the special marble multiple must not be 1");
+    }
+    Ok(())
+}
+
+fn play_out<B: MarbleBoard<u64>>(params: Params, rules: Rules) -> Result<Game<B>, Error> {
+    check_game(&params, &rules)?;
+    let mut game: Game<B> = Game::new(params, rules);
 
     while !game.complete() {
         game.play_turn().unwrap();
     }
 
-    game.winning_score()
+    Ok(game)
+}
+
+fn play<B: MarbleBoard<u64>>(params: Params, rules: Rules) -> Result<u64, Error> {
+    play_out::<B>(params, rules)?.winning_score()
+}
+
+/// The winning score of a game played under `rules`.
+pub fn play_with_rules(params: &Params, rules: &Rules) -> Result<u64, Error> {
+    scoreboard(params, rules)?
+        .winning_score()
+        .ok_or_else(|| err_msg("no player scored any points"))
+}
+
+/// Plays a whole game under `rules`, keeping every player's score and
+/// every scoring event.
+pub fn scoreboard(params: &Params, rules: &Rules) -> Result<Scoreboard, Error> {
+    Ok(play_out::<RingBoard<u64>>(*params, *rules)?.scoreboard())
+}
+
+/// Games with more marbles than this are too large to replay turn by turn.
+pub const REPLAY_LIMIT: u64 = 10_000;
+
+/// The board after every turn, one line per turn, as shown in the puzzle:
+///
+/// ```text
+/// [-] (0)
+/// [1]  0 (1)
+/// [2]  0 (2) 1
+/// [3]  0  2  1 (3)
+/// ```
+pub fn replay(params: &Params, rules: &Rules) -> Result<String, Error> {
+    check_game(params, rules)?;
+    if params.last_marble > REPLAY_LIMIT {
+        bail!(
+            "refusing to replay {} marbles; the limit is {}",
+            params.last_marble,
+            REPLAY_LIMIT
+        );
+    }
+
+    let mut game: Game<RingBoard<u64>> = Game::new(*params, *rules);
+    let width = params.last_marble.to_string().len() + 1;
+    let mut out = format!("[-]{}\n", game.render_board(width));
+
+    while !game.complete() {
+        let player = game.cur_player + 1;
+        game.play_turn().unwrap();
+        out += &format!("[{}]{}\n", player, game.render_board(width));
+    }
+
+    Ok(out)
+}
+
+#[aoc(day9, part1)]
+pub fn solve_part1(params: &Params) -> Result<u64, Error> {
+    play::<Board<u64>>(*params, Rules::default())
+}
+
+#[aoc(day9, part1, ring)]
+pub fn solve_part1_ring(params: &Params) -> Result<u64, Error> {
+    play::<RingBoard<u64>>(*params, Rules::default())
 }
 
 #[test]
 fn test_part1_sample() {
     let samples: [(&str, u64); 6] = [
-        (&"9 players; last marble is worth 25 points", 32),
-        (&"10 players; last marble is worth 1618 points", 8317),
-        (&"13 players; last marble is worth 7999 points", 146373),
-        (&"17 players; last marble is worth 1104 points", 2764),
-        (&"21 players; last marble is worth 6111 points", 54718),
-        (&"30 players; last marble is worth 5807 points", 37305),
+        ("9 players; last marble is worth 25 points", 32),
+        ("10 players; last marble is worth 1618 points", 8317),
+        ("13 players; last marble is worth 7999 points", 146373),
+        ("17 players; last marble is worth 1104 points", 2764),
+        ("21 players; last marble is worth 6111 points", 54718),
+        ("30 players; last marble is worth 5807 points", 37305),
     ];
 
     for sample in &samples {
         let params = input_generator(sample.0).unwrap();
         assert_eq!(solve_part1(&params).unwrap(), sample.1);
+        assert_eq!(solve_part1_ring(&params).unwrap(), sample.1);
     }
 }
 
 #[aoc(day9, part2)]
 pub fn solve_part2(params: &Params) -> Result<u64, Error> {
-    let big_params = Params {
-        players: params.players,
-        last_marble: params.last_marble * 100,
-    };
+    play::<Board<u64>>(params.scaled(PART2_MULTIPLIER)?, Rules::default())
+}
 
-    let mut game = Game::new(big_params);
+#[aoc(day9, part2, ring)]
+pub fn solve_part2_ring(params: &Params) -> Result<u64, Error> {
+    play::<RingBoard<u64>>(params.scaled(PART2_MULTIPLIER)?, Rules::default())
+}
 
-    while !game.complete() {
-        game.play_turn().unwrap();
+#[test]
+fn test_ring_board() {
+    let (mut ring, mut deque) = (RingBoard::new(), Board::new());
+    assert_eq!(ring.remove_marble(), None);
+
+    // 0 (4) 2 1 3, as the puzzle's board stands after marble 4
+    for marble in 0..5 {
+        ring.rotate(2);
+        ring.add_marble(marble);
     }
+    ring.rotate(-2);
+    assert_eq!(ring.remove_marble(), Some(3));
+    assert_eq!(ring.remove_marble(), Some(0));
+
+    // both boards agree through a mix of insertions, rotations and removals
+    ring = RingBoard::new();
+    for marble in 0..500u64 {
+        let offset = (marble * 7 % 11) as isize - 5;
+        ring.rotate(offset);
+        deque.rotate(offset);
+        if marble % 3 == 0 {
+            assert_eq!(ring.remove_marble(), deque.remove_marble());
+        } else {
+            ring.add_marble(marble);
+            deque.add_marble(marble);
+        }
+    }
+}
+
+#[test]
+fn test_custom_rules() {
+    let rules = |special_multiple, remove_offset, insert_offset| Rules {
+        special_multiple,
+        remove_offset,
+        insert_offset,
+    };
+
+    assert_eq!(
+        play_with_rules(&Params::new(9, 25), &Rules::default()).unwrap(),
+        32
+    );
+    assert_eq!(
+        play_with_rules(&Params::new(9, 25), &rules(5, -3, 1)).unwrap(),
+        46
+    );
+    assert_eq!(
+        play_with_rules(&Params::new(10, 1618), &rules(19, -5, 3)).unwrap(),
+        11598
+    );
+    assert_eq!(
+        play_with_rules(&Params::new(13, 7999), &rules(23, -9, 2)).unwrap(),
+        141870
+    );
+    // nobody scores before the first special marble
+    assert!(play_with_rules(&Params::new(10, 16), &Rules::default()).is_err());
+    assert!(play_with_rules(&Params::new(9, 25), &rules(1, -7, 2)).is_err());
+    let err = play_with_rules(&Params::new(9, 25), &rules(0, -7, 2)).unwrap_err();
+    assert_eq!(err.to_string(), "the special marble multiple must not be 0");
+
+    assert_eq!(Params::new(9, 25).scaled(4).unwrap().last_marble, 100);
+    assert!(Params::new(9, u64::MAX / 2).scaled(3).is_err());
+    assert!(play_with_rules(&Params::new(0, 25), &Rules::default()).is_err());
+}
+
+#[test]
+fn test_scoreboard() {
+    let board = scoreboard(&Params::new(9, 25), &Rules::default()).unwrap();
+    assert_eq!(board.winner(), Some(5));
+    assert_eq!(board.winning_score(), Some(32));
+    assert_eq!(board.scores.len(), 9);
+    assert_eq!(board.scores[1], (1, 0));
+    assert_eq!(
+        board.events,
+        vec![ScoringEvent {
+            turn: 23,
+            player: 5,
+            removed: 9
+        }]
+    );
+
+    let board = scoreboard(&Params::new(10, 16), &Rules::default()).unwrap();
+    assert_eq!(board.winner(), None);
+    assert_eq!(board.winning_score(), None);
+}
+
+#[test]
+fn test_replay() {
+    let out = replay(&Params::new(9, 25), &Rules::default()).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+
+    assert_eq!(lines.len(), 26);
+    assert_eq!(lines[0], "[-] (0)");
+    assert_eq!(lines[1], "[1]  0 (1)");
+    assert_eq!(lines[4], "[4]  0 (4) 2  1  3");
+    assert_eq!(
+        lines[22],
+        "[4]  0 16  8 17  4 18  9 19  2 20 10 21  5(22)11  1 12  6 13  3 14  7 15"
+    );
+    assert_eq!(
+        lines[23],
+        "[5]  0 16  8 17  4 18(19) 2 20 10 21  5 22 11  1 12  6 13  3 14  7 15"
+    );
+    assert_eq!(
+        lines[25],
+        "[7]  0 16  8 17  4 18 19  2 24 20(25)10 21  5 22 11  1 12  6 13  3 14  7 15"
+    );
 
-    game.winning_score()
+    assert!(replay(&Params::new(9, REPLAY_LIMIT + 1), &Rules::default()).is_err());
 }
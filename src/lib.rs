@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate aoc_runner_derive;
 
+pub mod cli;
 pub mod day1;
 pub mod day2;
 pub mod day3;
@@ -11,4 +12,7 @@ pub mod day7;
 pub mod day8;
 pub mod day9;
 
+#[cfg(test)]
+mod testutil;
+
 aoc_lib! { year = 2018 }
@@ -0,0 +1,11 @@
+/// A small xorshift generator, so tests needing random input don't pull in a
+/// rand dependency. `seed` must be non-zero.
+pub fn xorshift(seed: u32) -> impl FnMut() -> u32 {
+    let mut state = seed;
+    move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+    }
+}